@@ -1,5 +1,22 @@
-//! Hot-reload: watches the config file and reloads it on SIGHUP (Unix)
-//! or on a periodic timer (all platforms).
+//! Hot-reload: watches the config source and reloads it on SIGHUP (Unix),
+//! on a filesystem change, on a remote poll, or on a periodic timer (all
+//! platforms).
+//!
+//! The config can come from a local file or a remote document (central
+//! provisioning service, Consul KV endpoint exposed over HTTP, etc.) —
+//! see [`ConfigSource`]. Whichever it is, a fetched document runs through
+//! the same parse → `validate()` → [`HotFields`]/[`ListenerPlan`] diff →
+//! broadcast pipeline via [`apply_reload`], so a central service can drive
+//! user provisioning fleet-wide with the exact same behavior (including
+//! the auto-printed proxy links for newly added users) as editing the
+//! local file by hand.
+//!
+//! For a local file, the filesystem watch (via the `notify` crate) is
+//! what gives sub-second propagation in the common case; the timer tick
+//! is kept only as a fallback safety net for watchers that fail to start
+//! or miss an event. A remote source has no filesystem event to hook, so
+//! it's polled on `poll_interval` and skips re-parsing when the server's
+//! `ETag` says nothing changed.
 //!
 //! # What can be reloaded without restart
 //!
@@ -12,17 +29,43 @@
 //! | `access`  | All user/quota fields         | Effective immediately           |
 //!
 //! Fields that require re-binding sockets (`server.port`, `censorship.*`,
-//! `network.*`, `use_middle_proxy`) are **not** applied; a warning is emitted.
+//! `network.*`, `use_middle_proxy`) can't be applied in place, but they no
+//! longer just force a restart either: they're published as a
+//! [`ListenerPlan`] on a third channel, and it's up to the accept-loop
+//! owner to bind fresh listeners under the new plan, start routing new
+//! connections to them, and let listeners from the old plan drain their
+//! in-flight connections (up to `listener_drain_grace`) before closing them.
+//!
+//! Per-user quota usage (bytes transferred, unique IPs, connections
+//! opened) is accounted separately by [`super::persist::Persister`], which
+//! this module asks to drop usage for any user removed from `access.users`
+//! on reload — see `persist.rs` for why that state needs its own disk
+//! persistence instead of living only in `access`.
 //!
 //! # Usage
 //!
 //! ```rust,ignore
-//! let (config_rx, log_level_rx) = spawn_config_watcher(
-//!     PathBuf::from("config.toml"),
+//! let persister = Persister::load(PathBuf::from("quota_usage.json"), Duration::from_secs(10));
+//! persister.spawn_autosave();
+//! let metrics = Metrics::new();
+//! tokio::spawn(serve_metrics("0.0.0.0:9090".parse().unwrap(), metrics.clone(), persister.clone()));
+//!
+//! let (config_rx, log_level_rx, listener_rx) = spawn_config_watcher(
+//!     ConfigSource::File(PathBuf::from("config.toml")),
 //!     initial_config.clone(),
 //!     Duration::from_secs(60),
+//!     Duration::from_secs(30),
+//!     persister,
+//!     metrics,
 //! );
 //!
+//! // Or, to be driven by a central provisioning service instead:
+//! // ConfigSource::Remote {
+//! //     url: "https://provisioning.internal/telemt/node-7.toml".into(),
+//! //     poll_interval: Duration::from_secs(15),
+//! //     etag: None,
+//! // }
+//!
 //! // In each accept-loop, get a fresh snapshot per connection:
 //! let config = config_rx.borrow_and_update().clone();
 //!
@@ -34,17 +77,63 @@
 //!         filter_handle.reload(EnvFilter::new(level.to_filter_str())).ok();
 //!     }
 //! });
+//!
+//! // In the listener-owning task, rebind on a new plan and drain the old:
+//! tokio::spawn(async move {
+//!     loop {
+//!         listener_rx.changed().await.ok();
+//!         let plan = listener_rx.borrow().clone();
+//!         rebind_and_drain_old_listeners(plan).await;
+//!     }
+//! });
 //! ```
 
-use std::path::PathBuf;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
-use tokio::sync::watch;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, watch};
 use tracing::{error, info, warn};
 
 use crate::config::LogLevel;
+use crate::metrics::Metrics;
+use crate::protocol::transport::TransportKind;
 use super::load::ProxyConfig;
+use super::persist::Persister;
+
+/// Where `spawn_config_watcher` gets its config document from.
+#[derive(Debug, Clone)]
+pub enum ConfigSource {
+    /// A local file, watched via SIGHUP/filesystem-notify/interval exactly
+    /// as before remote sources existed.
+    File(PathBuf),
+    /// A document served over HTTP(S) — a central provisioning service, a
+    /// Consul KV endpoint fronted by `consul-template`-style HTTP, etc.
+    /// Polled on `poll_interval` since there's no filesystem event to
+    /// watch; `etag` seeds the first `If-None-Match`, letting an operator
+    /// resume from a known `ETag` across a process restart if they
+    /// persist it themselves (the watcher only tracks it in memory
+    /// otherwise).
+    Remote {
+        url: String,
+        poll_interval: Duration,
+        etag: Option<String>,
+    },
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::File(path) => write!(f, "{:?}", path),
+            ConfigSource::Remote { url, .. } => write!(f, "{}", url),
+        }
+    }
+}
 
 /// Fields that are safe to swap without restarting listeners.
 #[derive(Debug, Clone, PartialEq)]
@@ -74,228 +163,538 @@ impl HotFields {
     }
 }
 
-/// Warn if any non-hot fields changed (i.e. require restart).
-fn warn_non_hot_changes(old: &ProxyConfig, new: &ProxyConfig) {
+/// Window for coalescing a burst of filesystem events (e.g. an editor's
+/// "write to temp file + rename over the original") into a single reload.
+const FS_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Desired bind parameters for the listener(s), re-derived from the config
+/// on every reload. Every field here needs a fresh socket (or ME pool) to
+/// take effect, unlike [`HotFields`] which apply to already-running state.
+///
+/// The accept-loop owner is expected to treat a new value on this channel
+/// as: bind listeners matching the new plan, start handing them new
+/// connections, then let listeners from the previous plan finish their
+/// in-flight connections for up to `drain_grace` before closing them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListenerPlan {
+    pub port: u16,
+    pub tls_domain: String,
+    pub ipv4: bool,
+    pub ipv6: bool,
+    pub use_middle_proxy: bool,
+    /// TCP or KCP (reliable-over-UDP); like every other field here, picking
+    /// a different one needs a fresh bind/session, not an in-place swap.
+    pub transport: TransportKind,
+    /// How long old listeners are allowed to keep draining in-flight
+    /// connections before being force-closed.
+    pub drain_grace: Duration,
+}
+
+impl ListenerPlan {
+    pub fn from_config(cfg: &ProxyConfig, drain_grace: Duration) -> Self {
+        Self {
+            port: cfg.server.port,
+            tls_domain: cfg.censorship.tls_domain.clone(),
+            ipv4: cfg.network.ipv4,
+            ipv6: cfg.network.ipv6,
+            use_middle_proxy: cfg.general.use_middle_proxy,
+            transport: cfg.general.transport,
+            drain_grace,
+        }
+    }
+}
+
+/// Log each listener-affecting field that changed between `old` and `new`.
+fn log_listener_plan_changes(old: &ProxyConfig, new: &ProxyConfig) {
     if old.server.port != new.server.port {
-        warn!(
-            "config reload: server.port changed ({} → {}); restart required",
+        info!(
+            "config reload: server.port changed ({} → {}); rebinding listener",
             old.server.port, new.server.port
         );
     }
     if old.censorship.tls_domain != new.censorship.tls_domain {
-        warn!(
-            "config reload: censorship.tls_domain changed ('{}' → '{}'); restart required",
+        info!(
+            "config reload: censorship.tls_domain changed ('{}' → '{}'); rebinding listener",
             old.censorship.tls_domain, new.censorship.tls_domain
         );
     }
     if old.network.ipv4 != new.network.ipv4 || old.network.ipv6 != new.network.ipv6 {
-        warn!("config reload: network.ipv4/ipv6 changed; restart required");
+        info!("config reload: network.ipv4/ipv6 changed; rebinding listener");
     }
     if old.general.use_middle_proxy != new.general.use_middle_proxy {
-        warn!("config reload: use_middle_proxy changed; restart required");
+        info!("config reload: use_middle_proxy changed; rebinding listener");
+    }
+    if old.general.transport != new.general.transport {
+        info!(
+            "config reload: general.transport changed ({:?} → {:?}); rebinding listener",
+            old.general.transport, new.general.transport
+        );
+    }
+}
+
+/// Unlike [`ListenerPlan`]'s fields, a changed `general.privdrop` can't be
+/// applied by rebinding a listener: privileges are dropped once, right
+/// after every listener is bound, and a running process cannot regain
+/// root to redo the drop. Warn instead of silently ignoring it.
+fn warn_privdrop_changes(old: &ProxyConfig, new: &ProxyConfig) {
+    if old.general.privdrop != new.general.privdrop {
+        warn!("config reload: general.privdrop changed; restart required to take effect");
     }
 }
 
 /// Spawn the hot-reload watcher task.
 ///
+/// `listener_drain_grace` is the grace period carried on every
+/// [`ListenerPlan`] for how long a superseded listener may keep draining
+/// in-flight connections before the accept-loop owner force-closes it.
+///
+/// `persister` holds the per-user quota counters that must survive this
+/// reload; on every reload that changes `access.users`, usage for removed
+/// users is dropped from it (raised quotas for surviving users are left
+/// untouched — see [`Persister::reconcile`]).
+///
+/// `metrics` is incremented at each parse/validate/swap decision point
+/// (`telemt_config_reload_total{outcome=...}`) and on every `access.users`
+/// diff, so operators can watch reload health and user churn over
+/// `/metrics` instead of grepping logs.
+///
 /// Returns:
 /// - `watch::Receiver<Arc<ProxyConfig>>` — every accept-loop should call
 ///   `.borrow_and_update().clone()` per accepted connection.
 /// - `watch::Receiver<LogLevel>` — caller should watch this and apply changes
 ///   to the `tracing` reload handle (avoids lifetime/generic issues).
+/// - `watch::Receiver<ListenerPlan>` — the listener owner should watch this
+///   and perform a bind-new/drain-old rebind when it changes.
 pub fn spawn_config_watcher(
-    config_path: PathBuf,
+    source: ConfigSource,
     initial: Arc<ProxyConfig>,
     reload_interval: Duration,
-) -> (watch::Receiver<Arc<ProxyConfig>>, watch::Receiver<LogLevel>) {
+    listener_drain_grace: Duration,
+    persister: Arc<Persister>,
+    metrics: Arc<Metrics>,
+) -> (watch::Receiver<Arc<ProxyConfig>>, watch::Receiver<LogLevel>, watch::Receiver<ListenerPlan>) {
     let initial_level = initial.general.log_level.clone();
+    let initial_plan = ListenerPlan::from_config(&initial, listener_drain_grace);
+    metrics.record_user_diff(initial.access.users.len(), 0, 0);
     let (config_tx, config_rx) = watch::channel(initial);
     let (log_tx, log_rx)       = watch::channel(initial_level);
+    let (listener_tx, listener_rx) = watch::channel(initial_plan);
 
     tokio::spawn(async move {
-        // On Unix, also listen for SIGHUP.
-        #[cfg(unix)]
-        let mut sighup = {
-            use tokio::signal::unix::{signal, SignalKind};
-            signal(SignalKind::hangup()).expect("Failed to register SIGHUP handler")
-        };
+        match source {
+            ConfigSource::File(path) => {
+                run_file_watcher(
+                    path, reload_interval, listener_drain_grace,
+                    config_tx, log_tx, listener_tx, persister, metrics,
+                ).await;
+            }
+            ConfigSource::Remote { url, poll_interval, etag } => {
+                run_remote_watcher(
+                    url, poll_interval, etag, listener_drain_grace,
+                    config_tx, log_tx, listener_tx, persister, metrics,
+                ).await;
+            }
+        }
+    });
+
+    (config_rx, log_rx, listener_rx)
+}
+
+async fn run_file_watcher(
+    config_path: PathBuf,
+    reload_interval: Duration,
+    listener_drain_grace: Duration,
+    config_tx: watch::Sender<Arc<ProxyConfig>>,
+    log_tx: watch::Sender<LogLevel>,
+    listener_tx: watch::Sender<ListenerPlan>,
+    persister: Arc<Persister>,
+    metrics: Arc<Metrics>,
+) {
+    // On Unix, also listen for SIGHUP.
+    #[cfg(unix)]
+    let mut sighup = {
+        use tokio::signal::unix::{signal, SignalKind};
+        signal(SignalKind::hangup()).expect("Failed to register SIGHUP handler")
+    };
+
+    let mut interval = tokio::time::interval(reload_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
-        let mut interval = tokio::time::interval(reload_interval);
-        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    // Watch the parent directory rather than the file itself: an editor's
+    // atomic-replace save (write a temp file, then rename it over the
+    // original) changes the directory entry, not the original inode, so
+    // a watch on the inode alone would miss it.
+    let watch_dir = config_path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .map(|p| p.to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."));
+    let watch_name = config_path.file_name().map(|n| n.to_os_string());
 
-        loop {
-            // Wait for either a timer tick or SIGHUP.
-            #[cfg(unix)]
-            tokio::select! {
-                _ = interval.tick() => {},
-                _ = sighup.recv() => {
-                    info!("SIGHUP received — reloading config from {:?}", config_path);
+    let (fs_tx, mut fs_rx) = mpsc::channel::<()>(16);
+    let mut watcher: Option<RecommendedWatcher> = match RecommendedWatcher::new(
+        move |res: notify::Result<notify::Event>| match res {
+            Ok(event) => {
+                let relevant = watch_name.as_ref().map_or(true, |name| {
+                    event.paths.iter().any(|p| p.file_name() == Some(name.as_os_str()))
+                });
+                if relevant {
+                    let _ = fs_tx.blocking_send(());
                 }
             }
-            #[cfg(not(unix))]
-            interval.tick().await;
-
-            let new_cfg = match ProxyConfig::load(&config_path) {
-                Ok(c) => c,
-                Err(e) => {
-                    error!("config reload: failed to parse {:?}: {}", config_path, e);
-                    continue;
-                }
-            };
+            Err(e) => warn!("config hot-reload: filesystem watch error: {}", e),
+        },
+        notify::Config::default(),
+    ) {
+        Ok(w) => Some(w),
+        Err(e) => {
+            warn!("config hot-reload: failed to create filesystem watcher, falling back to the {:?} poll timer only: {}", reload_interval, e);
+            None
+        }
+    };
+    if let Some(w) = watcher.as_mut() {
+        if let Err(e) = w.watch(&watch_dir, RecursiveMode::NonRecursive) {
+            warn!("config hot-reload: failed to watch {:?}, falling back to the {:?} poll timer only: {}", watch_dir, reload_interval, e);
+            watcher = None;
+        }
+    }
+    let fs_watch_enabled = watcher.is_some();
 
-            if let Err(e) = new_cfg.validate() {
-                error!("config reload: validation failed: {}; keeping old config", e);
-                continue;
+    loop {
+        // Wait for a timer tick, SIGHUP, or a filesystem change.
+        #[cfg(unix)]
+        tokio::select! {
+            _ = interval.tick() => {},
+            _ = sighup.recv() => {
+                info!("SIGHUP received — reloading config from {:?}", config_path);
             }
+            Some(()) = fs_rx.recv(), if fs_watch_enabled => {
+                // Coalesce the rest of the "write + rename" burst.
+                while tokio::time::timeout(FS_DEBOUNCE, fs_rx.recv()).await.is_ok() {}
+                info!("filesystem change detected — reloading config from {:?}", config_path);
+            }
+        }
+        #[cfg(not(unix))]
+        tokio::select! {
+            _ = interval.tick() => {},
+            Some(()) = fs_rx.recv(), if fs_watch_enabled => {
+                while tokio::time::timeout(FS_DEBOUNCE, fs_rx.recv()).await.is_ok() {}
+                info!("filesystem change detected — reloading config from {:?}", config_path);
+            }
+        }
 
-            let old_cfg = config_tx.borrow().clone();
-            let old_hot = HotFields::from_config(&old_cfg);
-            let new_hot = HotFields::from_config(&new_cfg);
-
-            if old_hot == new_hot {
-                // Nothing changed in hot fields — skip silent tick.
+        let source_desc = format!("{:?}", config_path);
+        let new_cfg = match ProxyConfig::load(&config_path) {
+            Ok(c) => c,
+            Err(e) => {
+                error!("config reload: failed to parse {}: {}", source_desc, e);
+                metrics.record_reload_parse_failed();
                 continue;
             }
+        };
 
-            warn_non_hot_changes(&old_cfg, &new_cfg);
+        apply_reload(
+            new_cfg, &source_desc, listener_drain_grace,
+            &config_tx, &log_tx, &listener_tx, &persister, &metrics,
+        ).await;
+    }
+}
 
-            // ── Detailed diff logging ─────────────────────────────────────
+async fn run_remote_watcher(
+    url: String,
+    poll_interval: Duration,
+    mut etag: Option<String>,
+    listener_drain_grace: Duration,
+    config_tx: watch::Sender<Arc<ProxyConfig>>,
+    log_tx: watch::Sender<LogLevel>,
+    listener_tx: watch::Sender<ListenerPlan>,
+    persister: Arc<Persister>,
+    metrics: Arc<Metrics>,
+) {
+    let client = reqwest::Client::new();
+    let mut interval = tokio::time::interval(poll_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
-            // log_level
-            if old_hot.log_level != new_hot.log_level {
-                info!(
-                    "config reload: log_level: '{}' → '{}'",
-                    old_hot.log_level, new_hot.log_level
-                );
-                log_tx.send(new_hot.log_level.clone()).ok();
-            }
+    loop {
+        interval.tick().await;
 
-            // ad_tag
-            if old_hot.ad_tag != new_hot.ad_tag {
-                info!(
-                    "config reload: ad_tag: {} → {}",
-                    old_hot.ad_tag.as_deref().unwrap_or("none"),
-                    new_hot.ad_tag.as_deref().unwrap_or("none"),
-                );
+        let body = match fetch_remote_config(&client, &url, &etag).await {
+            Ok(FetchOutcome::NotModified) => continue,
+            Ok(FetchOutcome::Modified { body, etag: new_etag }) => {
+                etag = new_etag;
+                body
             }
+            Err(e) => {
+                error!("config reload: failed to fetch {}: {}; keeping old config", url, e);
+                metrics.record_reload_parse_failed();
+                continue;
+            }
+        };
 
-            // middle_proxy_pool_size
-            if old_hot.middle_proxy_pool_size != new_hot.middle_proxy_pool_size {
-                info!(
-                    "config reload: middle_proxy_pool_size: {} → {}",
-                    old_hot.middle_proxy_pool_size, new_hot.middle_proxy_pool_size,
-                );
+        // Reuse the exact same TOML-parsing/validation path a local file
+        // goes through, rather than second-guessing what ProxyConfig::load
+        // does beyond parsing (env interpolation, relative-path
+        // resolution, etc.) with a parallel from-string implementation.
+        let staged_path = remote_stage_path(persister.storage_dir(), &url);
+        if let Err(e) = stage_remote_config(&staged_path, &body).await {
+            error!("config reload: failed to stage fetched config from {}: {}", url, e);
+            metrics.record_reload_parse_failed();
+            continue;
+        }
+        let new_cfg = ProxyConfig::load(&staged_path);
+        let _ = tokio::fs::remove_file(&staged_path).await;
+        let new_cfg = match new_cfg {
+            Ok(c) => c,
+            Err(e) => {
+                error!("config reload: failed to parse fetched config from {}: {}", url, e);
+                metrics.record_reload_parse_failed();
+                continue;
             }
+        };
+
+        apply_reload(
+            new_cfg, &url, listener_drain_grace,
+            &config_tx, &log_tx, &listener_tx, &persister, &metrics,
+        ).await;
+    }
+}
+
+enum FetchOutcome {
+    NotModified,
+    Modified { body: String, etag: Option<String> },
+}
+
+/// GET `url`, sending `If-None-Match` when an `etag` is already known so
+/// an unchanged document costs a 304 instead of a full re-parse.
+async fn fetch_remote_config(
+    client: &reqwest::Client,
+    url: &str,
+    etag: &Option<String>,
+) -> Result<FetchOutcome, reqwest::Error> {
+    let mut req = client.get(url);
+    if let Some(tag) = etag {
+        req = req.header(reqwest::header::IF_NONE_MATCH, tag);
+    }
+    let resp = req.send().await?;
+    if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(FetchOutcome::NotModified);
+    }
+    let resp = resp.error_for_status()?;
+    let new_etag = resp.headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    let body = resp.text().await?;
+    Ok(FetchOutcome::Modified { body, etag: new_etag })
+}
+
+/// Path a fetched remote document is staged to before being handed to
+/// `ProxyConfig::load`, inside `storage_dir` (the quota persister's own
+/// directory, not the shared system temp dir — see `stage_remote_config`
+/// for why that distinction matters). Mixes in the current time and PID
+/// alongside the URL so concurrent/successive polls never reuse a name.
+fn remote_stage_path(storage_dir: &Path, url: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    SystemTime::now().hash(&mut hasher);
+    std::process::id().hash(&mut hasher);
+    storage_dir.join(format!("telemt-remote-config-{:016x}.toml.tmp", hasher.finish()))
+}
+
+/// Write `body` to `path`, refusing to follow anything already there.
+///
+/// The process may still be running as root at this point — privilege
+/// drop (`privdrop.rs`) only happens after listeners bind, well after the
+/// first remote-config poll can land — so a plain `tokio::fs::write` to a
+/// guessable path would let a local attacker pre-plant a symlink and
+/// redirect the write at an arbitrary file. `create_new` makes the open
+/// fail instead of following it.
+async fn stage_remote_config(path: &Path, body: &str) -> io::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new().write(true).create_new(true).open(path).await?;
+    file.write_all(body.as_bytes()).await
+}
+
+/// Validate a newly-fetched config, diff it against the current one, and
+/// broadcast it — the pipeline shared by every [`ConfigSource`]. On
+/// validation failure the current good config is left untouched, exactly
+/// as for a bad local file.
+#[allow(clippy::too_many_arguments)]
+async fn apply_reload(
+    new_cfg: ProxyConfig,
+    source_desc: &str,
+    listener_drain_grace: Duration,
+    config_tx: &watch::Sender<Arc<ProxyConfig>>,
+    log_tx: &watch::Sender<LogLevel>,
+    listener_tx: &watch::Sender<ListenerPlan>,
+    persister: &Persister,
+    metrics: &Metrics,
+) {
+    if let Err(e) = new_cfg.validate() {
+        error!("config reload: validation failed ({}): {}; keeping old config", source_desc, e);
+        metrics.record_reload_validation_failed();
+        return;
+    }
+
+    let old_cfg = config_tx.borrow().clone();
+    let old_hot = HotFields::from_config(&old_cfg);
+    let new_hot = HotFields::from_config(&new_cfg);
+    let new_plan = ListenerPlan::from_config(&new_cfg, listener_drain_grace);
+    let plan_changed = *listener_tx.borrow() != new_plan;
+    let privdrop_changed = old_cfg.general.privdrop != new_cfg.general.privdrop;
+
+    if old_hot == new_hot && !plan_changed && !privdrop_changed {
+        // Nothing changed in hot fields or listener-affecting fields —
+        // skip silent tick.
+        return;
+    }
+
+    if plan_changed {
+        log_listener_plan_changes(&old_cfg, &new_cfg);
+        listener_tx.send(new_plan).ok();
+    }
+    if privdrop_changed {
+        warn_privdrop_changes(&old_cfg, &new_cfg);
+    }
+
+    // ── Detailed diff logging ─────────────────────────────────────
+
+    // log_level
+    if old_hot.log_level != new_hot.log_level {
+        info!(
+            "config reload: log_level: '{}' → '{}'",
+            old_hot.log_level, new_hot.log_level
+        );
+        log_tx.send(new_hot.log_level.clone()).ok();
+    }
+
+    // ad_tag
+    if old_hot.ad_tag != new_hot.ad_tag {
+        info!(
+            "config reload: ad_tag: {} → {}",
+            old_hot.ad_tag.as_deref().unwrap_or("none"),
+            new_hot.ad_tag.as_deref().unwrap_or("none"),
+        );
+    }
+
+    // middle_proxy_pool_size
+    if old_hot.middle_proxy_pool_size != new_hot.middle_proxy_pool_size {
+        info!(
+            "config reload: middle_proxy_pool_size: {} → {}",
+            old_hot.middle_proxy_pool_size, new_hot.middle_proxy_pool_size,
+        );
+    }
+
+    // me_keepalive
+    if old_hot.me_keepalive_enabled != new_hot.me_keepalive_enabled
+        || old_hot.me_keepalive_interval_secs != new_hot.me_keepalive_interval_secs
+        || old_hot.me_keepalive_jitter_secs != new_hot.me_keepalive_jitter_secs
+        || old_hot.me_keepalive_payload_random != new_hot.me_keepalive_payload_random
+    {
+        info!(
+            "config reload: me_keepalive: enabled={} interval={}s jitter={}s random_payload={}",
+            new_hot.me_keepalive_enabled,
+            new_hot.me_keepalive_interval_secs,
+            new_hot.me_keepalive_jitter_secs,
+            new_hot.me_keepalive_payload_random,
+        );
+    }
+
+    // access.users — added / removed / changed
+    if old_hot.access.users != new_hot.access.users {
+        let added: Vec<&String> = new_hot.access.users.keys()
+            .filter(|u| !old_hot.access.users.contains_key(*u))
+            .collect();
+        let removed: Vec<&String> = old_hot.access.users.keys()
+            .filter(|u| !new_hot.access.users.contains_key(*u))
+            .collect();
+        let changed: Vec<&String> = new_hot.access.users.keys()
+            .filter(|u| {
+                old_hot.access.users.get(*u)
+                    .map(|old_s| old_s != &new_hot.access.users[*u])
+                    .unwrap_or(false)
+            })
+            .collect();
+
+        if !added.is_empty() {
+            let names: Vec<&str> = added.iter().map(|s| s.as_str()).collect();
+            info!("config reload: users added: [{}]", names.join(", "));
 
-            // me_keepalive
-            if old_hot.me_keepalive_enabled != new_hot.me_keepalive_enabled
-                || old_hot.me_keepalive_interval_secs != new_hot.me_keepalive_interval_secs
-                || old_hot.me_keepalive_jitter_secs != new_hot.me_keepalive_jitter_secs
-                || old_hot.me_keepalive_payload_random != new_hot.me_keepalive_payload_random
-            {
-                info!(
-                    "config reload: me_keepalive: enabled={} interval={}s jitter={}s random_payload={}",
-                    new_hot.me_keepalive_enabled,
-                    new_hot.me_keepalive_interval_secs,
-                    new_hot.me_keepalive_jitter_secs,
-                    new_hot.me_keepalive_payload_random,
-                );
+            // Print TG proxy links for each newly added user.
+            let host = new_cfg.general.links.public_host.as_deref()
+                .unwrap_or("YOUR_SERVER_IP");
+            let port = new_cfg.general.links.public_port
+                .unwrap_or(new_cfg.server.port);
+            let tls_domain = &new_cfg.censorship.tls_domain;
+            let mut tls_domains = vec![tls_domain.clone()];
+            for d in &new_cfg.censorship.tls_domains {
+                if !tls_domains.contains(d) { tls_domains.push(d.clone()); }
             }
 
-            // access.users — added / removed / changed
-            if old_hot.access.users != new_hot.access.users {
-                let added: Vec<&String> = new_hot.access.users.keys()
-                    .filter(|u| !old_hot.access.users.contains_key(*u))
-                    .collect();
-                let removed: Vec<&String> = old_hot.access.users.keys()
-                    .filter(|u| !new_hot.access.users.contains_key(*u))
-                    .collect();
-                let changed: Vec<&String> = new_hot.access.users.keys()
-                    .filter(|u| {
-                        old_hot.access.users.get(*u)
-                            .map(|old_s| old_s != &new_hot.access.users[*u])
-                            .unwrap_or(false)
-                    })
-                    .collect();
-
-                if !added.is_empty() {
-                    let names: Vec<&str> = added.iter().map(|s| s.as_str()).collect();
-                    info!("config reload: users added: [{}]", names.join(", "));
-
-                    // Print TG proxy links for each newly added user.
-                    let host = new_cfg.general.links.public_host.as_deref()
-                        .unwrap_or("YOUR_SERVER_IP");
-                    let port = new_cfg.general.links.public_port
-                        .unwrap_or(new_cfg.server.port);
-                    let tls_domain = &new_cfg.censorship.tls_domain;
-                    let mut tls_domains = vec![tls_domain.clone()];
-                    for d in &new_cfg.censorship.tls_domains {
-                        if !tls_domains.contains(d) { tls_domains.push(d.clone()); }
+            for user in &added {
+                if let Some(secret) = new_hot.access.users.get(*user) {
+                    info!(target: "telemt::links", "--- New user: {} ---", user);
+                    if new_cfg.general.modes.classic {
+                        info!(
+                            target: "telemt::links",
+                            "  Classic: tg://proxy?server={}&port={}&secret={}",
+                            host, port, secret
+                        );
                     }
-
-                    for user in &added {
-                        if let Some(secret) = new_hot.access.users.get(*user) {
-                            info!(target: "telemt::links", "--- New user: {} ---", user);
-                            if new_cfg.general.modes.classic {
-                                info!(
-                                    target: "telemt::links",
-                                    "  Classic: tg://proxy?server={}&port={}&secret={}",
-                                    host, port, secret
-                                );
-                            }
-                            if new_cfg.general.modes.secure {
-                                info!(
-                                    target: "telemt::links",
-                                    "  DD:      tg://proxy?server={}&port={}&secret=dd{}",
-                                    host, port, secret
-                                );
-                            }
-                            if new_cfg.general.modes.tls {
-                                for domain in &tls_domains {
-                                    let domain_hex = hex::encode(domain.as_bytes());
-                                    info!(
-                                        target: "telemt::links",
-                                        "  EE-TLS:  tg://proxy?server={}&port={}&secret=ee{}{}",
-                                        host, port, secret, domain_hex
-                                    );
-                                }
-                            }
-                            info!(target: "telemt::links", "--------------------");
+                    if new_cfg.general.modes.secure {
+                        info!(
+                            target: "telemt::links",
+                            "  DD:      tg://proxy?server={}&port={}&secret=dd{}",
+                            host, port, secret
+                        );
+                    }
+                    if new_cfg.general.modes.tls {
+                        for domain in &tls_domains {
+                            let domain_hex = hex::encode(domain.as_bytes());
+                            info!(
+                                target: "telemt::links",
+                                "  EE-TLS:  tg://proxy?server={}&port={}&secret=ee{}{}",
+                                host, port, secret, domain_hex
+                            );
                         }
                     }
-                }
-                if !removed.is_empty() {
-                    let names: Vec<&str> = removed.iter().map(|s| s.as_str()).collect();
-                    info!("config reload: users removed: [{}]", names.join(", "));
-                }
-                if !changed.is_empty() {
-                    let names: Vec<&str> = changed.iter().map(|s| s.as_str()).collect();
-                    info!("config reload: users secret changed: [{}]", names.join(", "));
+                    info!(target: "telemt::links", "--------------------");
                 }
             }
-
-            // access quotas / limits
-            if old_hot.access.user_max_tcp_conns != new_hot.access.user_max_tcp_conns {
-                info!("config reload: user_max_tcp_conns updated ({} entries)",
-                    new_hot.access.user_max_tcp_conns.len());
-            }
-            if old_hot.access.user_expirations != new_hot.access.user_expirations {
-                info!("config reload: user_expirations updated ({} entries)",
-                    new_hot.access.user_expirations.len());
-            }
-            if old_hot.access.user_data_quota != new_hot.access.user_data_quota {
-                info!("config reload: user_data_quota updated ({} entries)",
-                    new_hot.access.user_data_quota.len());
-            }
-            if old_hot.access.user_max_unique_ips != new_hot.access.user_max_unique_ips {
-                info!("config reload: user_max_unique_ips updated ({} entries)",
-                    new_hot.access.user_max_unique_ips.len());
-            }
-
-            // Broadcast the new config snapshot.
-            config_tx.send(Arc::new(new_cfg)).ok();
         }
-    });
+        if !removed.is_empty() {
+            let names: Vec<&str> = removed.iter().map(|s| s.as_str()).collect();
+            info!("config reload: users removed: [{}]", names.join(", "));
+        }
+        // Drop persisted quota usage for anyone no longer in access.users;
+        // a raised quota for a surviving user is left alone (reconcile
+        // only ever drops, never resets).
+        persister.reconcile(&new_hot.access).await;
+        metrics.record_user_diff(new_hot.access.users.len(), added.len(), removed.len());
+        if !changed.is_empty() {
+            let names: Vec<&str> = changed.iter().map(|s| s.as_str()).collect();
+            info!("config reload: users secret changed: [{}]", names.join(", "));
+        }
+    }
+
+    // access quotas / limits
+    if old_hot.access.user_max_tcp_conns != new_hot.access.user_max_tcp_conns {
+        info!("config reload: user_max_tcp_conns updated ({} entries)",
+            new_hot.access.user_max_tcp_conns.len());
+    }
+    if old_hot.access.user_expirations != new_hot.access.user_expirations {
+        info!("config reload: user_expirations updated ({} entries)",
+            new_hot.access.user_expirations.len());
+    }
+    if old_hot.access.user_data_quota != new_hot.access.user_data_quota {
+        info!("config reload: user_data_quota updated ({} entries)",
+            new_hot.access.user_data_quota.len());
+    }
+    if old_hot.access.user_max_unique_ips != new_hot.access.user_max_unique_ips {
+        info!("config reload: user_max_unique_ips updated ({} entries)",
+            new_hot.access.user_max_unique_ips.len());
+    }
 
-    (config_rx, log_rx)
+    // Broadcast the new config snapshot.
+    config_tx.send(Arc::new(new_cfg)).ok();
+    metrics.record_reload_success();
 }
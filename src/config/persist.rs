@@ -0,0 +1,190 @@
+//! Persists per-user quota-accounting counters (bytes transferred, unique
+//! source IPs, and connections opened) to disk so `access.*` quotas
+//! survive both a hot-reload and a full process restart — without this, a
+//! user could reset `user_data_quota`/`user_max_unique_ips` simply by
+//! forcing the process to restart.
+//!
+//! The relay/session layer that enforces quotas is the one calling
+//! [`Persister::record_bytes`], [`Persister::record_ip`], and
+//! [`Persister::record_conn_opened`] as traffic happens; this module only
+//! owns accumulating those counters in memory and flushing them to `path`
+//! as JSON on an atomic temp-file-then-rename write, throttled to
+//! `save_interval` so a burst of traffic doesn't thrash disk.
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+use super::AccessConfig;
+
+/// Accumulated usage for a single user — the part of quota accounting
+/// that needs to outlive a restart.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserUsage {
+    pub bytes_transferred: u64,
+    pub unique_ips: HashSet<IpAddr>,
+    pub conns_opened: u64,
+}
+
+pub type UsageMap = HashMap<String, UserUsage>;
+
+/// Periodically snapshots [`UserUsage`] per user to `path`, and restores
+/// it on construction so quotas survive both hot-reloads and restarts.
+pub struct Persister {
+    path: PathBuf,
+    state: Mutex<UsageMap>,
+    dirty: AtomicBool,
+    save_interval: Duration,
+    last_saved: Mutex<Instant>,
+}
+
+impl Persister {
+    /// Load persisted usage from `path` if it exists. A missing or
+    /// unreadable file just starts from empty state — this is accounting,
+    /// not config, so a bad file shouldn't block startup.
+    pub fn load(path: PathBuf, save_interval: Duration) -> Arc<Self> {
+        let state = match std::fs::read(&path) {
+            Ok(bytes) => match serde_json::from_slice::<UsageMap>(&bytes) {
+                Ok(m) => m,
+                Err(e) => {
+                    warn!(
+                        "quota persister: failed to parse {:?}, starting from empty state: {}",
+                        path, e
+                    );
+                    HashMap::new()
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                warn!(
+                    "quota persister: failed to read {:?}, starting from empty state: {}",
+                    path, e
+                );
+                HashMap::new()
+            }
+        };
+        info!(
+            "quota persister: restored usage for {} user(s) from {:?}",
+            state.len(), path
+        );
+        Arc::new(Self {
+            path,
+            state: Mutex::new(state),
+            dirty: AtomicBool::new(false),
+            save_interval,
+            last_saved: Mutex::new(Instant::now() - save_interval),
+        })
+    }
+
+    /// Directory `path` lives in, i.e. wherever the operator already
+    /// trusted us to write persistent state. Other reload machinery (the
+    /// remote-config fetch loop) stages its own temp files here instead of
+    /// the shared system temp dir, since this directory is presumably
+    /// private to the proxy rather than world-writable.
+    pub fn storage_dir(&self) -> &Path {
+        self.path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."))
+    }
+
+    /// Spawn a background ticker that flushes dirty state every
+    /// `save_interval`, independent of config-reload events — traffic
+    /// accumulates continuously, reloads don't.
+    pub fn spawn_autosave(self: &Arc<Self>) {
+        let persister = self.clone();
+        let interval = persister.save_interval;
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                persister.maybe_save().await;
+            }
+        });
+    }
+
+    pub async fn record_bytes(&self, user: &str, n: u64) {
+        let mut state = self.state.lock().await;
+        state.entry(user.to_string()).or_default().bytes_transferred += n;
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub async fn record_ip(&self, user: &str, ip: IpAddr) {
+        let mut state = self.state.lock().await;
+        state.entry(user.to_string()).or_default().unique_ips.insert(ip);
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub async fn record_conn_opened(&self, user: &str) {
+        let mut state = self.state.lock().await;
+        state.entry(user.to_string()).or_default().conns_opened += 1;
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub async fn usage(&self, user: &str) -> UserUsage {
+        self.state.lock().await.get(user).cloned().unwrap_or_default()
+    }
+
+    /// A clone of the full per-user usage map, for the metrics endpoint.
+    pub async fn snapshot(&self) -> UsageMap {
+        self.state.lock().await.clone()
+    }
+
+    /// Drop persisted usage for any user no longer present in
+    /// `access.users`. A raised `user_data_quota` for a still-present user
+    /// is a no-op here — accumulated usage is deliberately left untouched
+    /// so it keeps counting against the new, higher limit.
+    pub async fn reconcile(&self, access: &AccessConfig) {
+        let mut state = self.state.lock().await;
+        let before = state.len();
+        state.retain(|user, _| access.users.contains_key(user));
+        let dropped = before - state.len();
+        drop(state);
+        if dropped > 0 {
+            self.dirty.store(true, Ordering::Relaxed);
+            info!("quota persister: dropped usage for {} removed user(s)", dropped);
+        }
+    }
+
+    /// Flush to disk if dirty and `save_interval` has elapsed since the
+    /// last write. Writes are atomic: serialize, write to a sibling
+    /// `.tmp` file, then rename over `path`.
+    pub async fn maybe_save(&self) {
+        if !self.dirty.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut last_saved = self.last_saved.lock().await;
+        if last_saved.elapsed() < self.save_interval {
+            return;
+        }
+        if let Err(e) = self.save().await {
+            error!("quota persister: failed to save {:?}: {}", self.path, e);
+            return;
+        }
+        *last_saved = Instant::now();
+        self.dirty.store(false, Ordering::Relaxed);
+    }
+
+    async fn save(&self) -> io::Result<()> {
+        let snapshot = self.state.lock().await.clone();
+        let json = serde_json::to_vec_pretty(&snapshot)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let tmp_path = tmp_path_for(&self.path);
+        tokio::fs::write(&tmp_path, &json).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+        Ok(())
+    }
+}
+
+fn tmp_path_for(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    PathBuf::from(tmp)
+}
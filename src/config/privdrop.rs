@@ -0,0 +1,145 @@
+//! Optional post-bind privilege drop, so the proxy can be started as root
+//! to bind a privileged port (e.g. 443 for EE-TLS camouflage) and then give
+//! up root before it ever serves a byte of traffic.
+//!
+//! This is a one-shot operation performed once, immediately after all
+//! listeners are bound, by whatever owns the accept loop — unlike the
+//! fields on [`super::hot_reload::ListenerPlan`], there's no "rebind" story
+//! for a privilege drop: once the process has called `setuid`, it cannot
+//! get root back without a full restart, so [`PrivDropConfig`] changes are
+//! treated as restart-required rather than folded into the listener-rebind
+//! contract.
+//!
+//! Because [`super::hot_reload::spawn_config_watcher`] keeps re-reading the
+//! config file from disk for the lifetime of the process, a `chroot` here
+//! would otherwise silently break hot-reload the moment it fires: the
+//! absolute path the watcher was given resolves against the *old* root, so
+//! re-opening it post-chroot double-prefixes it (e.g. `/srv/jail/etc/t.toml`
+//! becomes `<chroot>/srv/jail/etc/t.toml`). [`drop_privileges`] returns the
+//! path as it resolves *from inside* the chroot, which the caller must pass
+//! to `spawn_config_watcher` instead of the pre-drop path.
+
+use std::ffi::CString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tracing::info;
+
+/// `general.privdrop` — present only when the operator wants to start
+/// privileged and drop down before serving traffic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivDropConfig {
+    pub user: String,
+    pub group: Option<String>,
+    pub chroot: Option<String>,
+}
+
+/// Resolve `cfg`, chroot (if requested), and drop to the target
+/// user/group. Must be called after every listener is bound — there's no
+/// way to bind a port below 1024 afterward.
+///
+/// Returns the config path as it will resolve *after* the chroot, i.e. the
+/// path the caller must hand to `spawn_config_watcher` for reloads to keep
+/// working. When `cfg.chroot` is `None` this is just `config_path` back
+/// unchanged.
+pub fn drop_privileges(cfg: &PrivDropConfig, config_path: &Path) -> io::Result<PathBuf> {
+    let post_chroot_config_path = match &cfg.chroot {
+        Some(chroot_dir) => post_chroot_config_path(config_path, Path::new(chroot_dir))?,
+        None => config_path.to_path_buf(),
+    };
+
+    let user = CString::new(cfg.user.as_str())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "privdrop user contains a NUL byte"))?;
+    // SAFETY: `getpwnam` is read-only and `user` is a valid, NUL-terminated
+    // C string for the duration of the call.
+    let pw = unsafe { libc::getpwnam(user.as_ptr()) };
+    if pw.is_null() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("privdrop: no such user '{}'", cfg.user),
+        ));
+    }
+    // SAFETY: `pw` was just checked non-null and points at a `passwd`
+    // struct valid until the next `getpwnam`/`getpwuid` call, which we
+    // don't make before reading out the fields we need.
+    let (target_uid, passwd_gid) = unsafe { ((*pw).pw_uid, (*pw).pw_gid) };
+
+    let target_gid = match &cfg.group {
+        Some(group) => {
+            let group_c = CString::new(group.as_str()).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "privdrop group contains a NUL byte")
+            })?;
+            // SAFETY: same contract as `getpwnam` above.
+            let gr = unsafe { libc::getgrnam(group_c.as_ptr()) };
+            if gr.is_null() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("privdrop: no such group '{}'", group),
+                ));
+            }
+            // SAFETY: `gr` was just checked non-null.
+            unsafe { (*gr).gr_gid }
+        }
+        None => passwd_gid,
+    };
+
+    if let Some(chroot_dir) = &cfg.chroot {
+        let chroot_c = CString::new(chroot_dir.as_str())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "privdrop chroot contains a NUL byte"))?;
+        // SAFETY: `chroot_c` is a valid C string; return value is checked below.
+        if unsafe { libc::chroot(chroot_c.as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: no arguments to misuse; return value is checked below.
+        if unsafe { libc::chdir(CString::new("/").unwrap().as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    // Drop supplementary groups before the primary gid/uid, and gid before
+    // uid — once `setuid` succeeds we've lost the privilege to change
+    // either.
+    // SAFETY: `user.as_ptr()` is valid for the call; `target_gid` is a
+    // plain integer.
+    if unsafe { libc::initgroups(user.as_ptr(), target_gid as libc::gid_t) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `target_gid` was resolved above from a real passwd/group entry.
+    if unsafe { libc::setgid(target_gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `target_uid` was resolved above from a real passwd entry.
+    if unsafe { libc::setuid(target_uid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    info!(
+        "privdrop: dropped to uid={} gid={}{}",
+        target_uid,
+        target_gid,
+        cfg.chroot.as_deref().map(|d| format!(", chroot={}", d)).unwrap_or_default()
+    );
+    Ok(post_chroot_config_path)
+}
+
+/// A `chroot` replaces the process's view of the filesystem root, so
+/// anything [`super::hot_reload::spawn_config_watcher`] opens by path
+/// afterward — including re-reading `config_path` on every reload — has to
+/// resolve inside the new root. Requires `config_path` to live under
+/// `chroot_dir`, then strips the chroot prefix so the returned path is the
+/// one that will actually resolve once inside it (e.g. `/srv/jail/etc/t.toml`
+/// under chroot dir `/srv/jail` becomes `/etc/t.toml`).
+fn post_chroot_config_path(config_path: &Path, chroot_dir: &Path) -> io::Result<PathBuf> {
+    let canonical_chroot = chroot_dir.canonicalize()?;
+    let canonical_config = config_path.canonicalize()?;
+    let relative = canonical_config.strip_prefix(&canonical_chroot).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "privdrop: config path {:?} is not inside chroot dir {:?}; hot-reload would fail to re-read it after the drop",
+                canonical_config, canonical_chroot
+            ),
+        )
+    })?;
+    Ok(Path::new("/").join(relative))
+}
@@ -0,0 +1,240 @@
+//! Prometheus text-format metrics, exposed over a hand-rolled `/metrics`
+//! HTTP endpoint.
+//!
+//! Deliberately doesn't pull in a web framework or a Prometheus client
+//! library for one GET route: [`Metrics`] is a handful of atomics plus a
+//! text-exposition-format writer, in the same spirit as the rest of this
+//! crate's dependency-light infra ([`crate::transport::middle_proxy`]'s
+//! hand-rolled token bucket and frame tap). [`serve_metrics`] reads just enough of
+//! the HTTP/1.1 request line to tell `GET /metrics` apart from everything
+//! else.
+//!
+//! Per-user byte/connection gauges live here rather than duplicating
+//! state: bytes transferred reuses [`crate::config::persist::Persister`]'s
+//! already-persisted per-user counters (the quota accounting this crate
+//! already keeps), while active TCP connections — a live, non-persisted
+//! gauge — are tracked locally via [`Metrics::conn_opened`] /
+//! [`Metrics::conn_closed`].
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{error, info, warn};
+
+use crate::config::persist::Persister;
+
+/// Which transport mode a connection was detected as, for the
+/// `telemt_transport_bytes_total` counter. Matches the three link types
+/// `spawn_config_watcher` already prints for newly-added users (Classic /
+/// DD / EE-TLS).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransportMode {
+    Classic,
+    Secure,
+    Tls,
+}
+
+impl TransportMode {
+    fn label(self) -> &'static str {
+        match self {
+            TransportMode::Classic => "classic",
+            TransportMode::Secure => "dd",
+            TransportMode::Tls => "ee_tls",
+        }
+    }
+}
+
+/// Process-wide metrics registry. Cheap to clone (`Arc` internally via
+/// the caller holding an `Arc<Metrics>`); every field is lock-free except
+/// the per-user active-connection gauge.
+pub struct Metrics {
+    reload_success: AtomicU64,
+    reload_parse_failed: AtomicU64,
+    reload_validation_failed: AtomicU64,
+    users_added_total: AtomicU64,
+    users_removed_total: AtomicU64,
+    active_users: AtomicI64,
+    transport_bytes: [AtomicU64; 3],
+    active_tcp_conns: Mutex<HashMap<String, i64>>,
+}
+
+impl Metrics {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            reload_success: AtomicU64::new(0),
+            reload_parse_failed: AtomicU64::new(0),
+            reload_validation_failed: AtomicU64::new(0),
+            users_added_total: AtomicU64::new(0),
+            users_removed_total: AtomicU64::new(0),
+            active_users: AtomicI64::new(0),
+            transport_bytes: [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)],
+            active_tcp_conns: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn record_reload_success(&self) {
+        self.reload_success.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_reload_parse_failed(&self) {
+        self.reload_parse_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_reload_validation_failed(&self) {
+        self.reload_validation_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Set the current active-user gauge and add to the added/removed
+    /// reload-scoped counters.
+    pub fn record_user_diff(&self, active: usize, added: usize, removed: usize) {
+        self.active_users.store(active as i64, Ordering::Relaxed);
+        self.users_added_total.fetch_add(added as u64, Ordering::Relaxed);
+        self.users_removed_total.fetch_add(removed as u64, Ordering::Relaxed);
+    }
+
+    /// Feed a byte count into the counter for the mode a connection was
+    /// detected as. Called from the MTProto frame/obfuscation layer once
+    /// it has identified the connection's transport (classic / DD /
+    /// EE-TLS) by its secret prefix.
+    pub fn record_transport_bytes(&self, mode: TransportMode, n: u64) {
+        self.transport_bytes[mode as usize].fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn conn_opened(&self, user: &str) {
+        let mut active = self.active_tcp_conns.lock().unwrap();
+        *active.entry(user.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn conn_closed(&self, user: &str) {
+        let mut active = self.active_tcp_conns.lock().unwrap();
+        if let Some(n) = active.get_mut(user) {
+            *n -= 1;
+        }
+    }
+
+    /// Render the registry plus `persister`'s per-user byte counters as
+    /// Prometheus text exposition format.
+    pub async fn render(&self, persister: &Persister) -> String {
+        let mut out = String::with_capacity(2048);
+
+        let _ = writeln!(out, "# HELP telemt_config_reload_total Config reload attempts by outcome.");
+        let _ = writeln!(out, "# TYPE telemt_config_reload_total counter");
+        let _ = writeln!(out, "telemt_config_reload_total{{outcome=\"success\"}} {}",
+            self.reload_success.load(Ordering::Relaxed));
+        let _ = writeln!(out, "telemt_config_reload_total{{outcome=\"parse_failed\"}} {}",
+            self.reload_parse_failed.load(Ordering::Relaxed));
+        let _ = writeln!(out, "telemt_config_reload_total{{outcome=\"validation_failed\"}} {}",
+            self.reload_validation_failed.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# HELP telemt_active_users Current number of configured users.");
+        let _ = writeln!(out, "# TYPE telemt_active_users gauge");
+        let _ = writeln!(out, "telemt_active_users {}", self.active_users.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# HELP telemt_users_added_total Users added across all reloads.");
+        let _ = writeln!(out, "# TYPE telemt_users_added_total counter");
+        let _ = writeln!(out, "telemt_users_added_total {}", self.users_added_total.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# HELP telemt_users_removed_total Users removed across all reloads.");
+        let _ = writeln!(out, "# TYPE telemt_users_removed_total counter");
+        let _ = writeln!(out, "telemt_users_removed_total {}", self.users_removed_total.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# HELP telemt_transport_bytes_total Bytes relayed, by detected transport mode.");
+        let _ = writeln!(out, "# TYPE telemt_transport_bytes_total counter");
+        for mode in [TransportMode::Classic, TransportMode::Secure, TransportMode::Tls] {
+            let _ = writeln!(out, "telemt_transport_bytes_total{{mode=\"{}\"}} {}",
+                mode.label(), self.transport_bytes[mode as usize].load(Ordering::Relaxed));
+        }
+
+        let _ = writeln!(out, "# HELP telemt_user_bytes_transferred_total Bytes transferred, per user.");
+        let _ = writeln!(out, "# TYPE telemt_user_bytes_transferred_total counter");
+        for (user, usage) in persister.snapshot().await {
+            let _ = writeln!(out, "telemt_user_bytes_transferred_total{{user=\"{}\"}} {}",
+                escape_label_value(&user), usage.bytes_transferred);
+        }
+
+        let _ = writeln!(out, "# HELP telemt_user_active_tcp_conns Current open TCP connections, per user.");
+        let _ = writeln!(out, "# TYPE telemt_user_active_tcp_conns gauge");
+        for (user, n) in self.active_tcp_conns.lock().unwrap().iter() {
+            let _ = writeln!(out, "telemt_user_active_tcp_conns{{user=\"{}\"}} {}", escape_label_value(user), n);
+        }
+
+        out
+    }
+}
+
+/// Escape a label value per the Prometheus text-exposition format so an
+/// operator-controlled `user` name (not otherwise constrained anywhere in
+/// this tree) can't break the line it's interpolated into — or the whole
+/// scrape, since one malformed line trips most parsers for the entire
+/// response. Per the spec: `\` -> `\\`, `"` -> `\"`, newline -> `\n`.
+fn escape_label_value(value: &str) -> std::borrow::Cow<'_, str> {
+    if !value.contains(['\\', '"', '\n']) {
+        return std::borrow::Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    std::borrow::Cow::Owned(escaped)
+}
+
+/// Serve `/metrics` on `addr` until the process exits. Anything other
+/// than `GET /metrics` gets a bare 404; this is a diagnostics endpoint,
+/// not a general HTTP server.
+pub async fn serve_metrics(addr: SocketAddr, metrics: Arc<Metrics>, persister: Arc<Persister>) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            error!("metrics: failed to bind {}: {}", addr, e);
+            return;
+        }
+    };
+    info!("metrics: serving /metrics on {}", addr);
+
+    loop {
+        let (mut sock, _) = match listener.accept().await {
+            Ok(pair) => pair,
+            Err(e) => {
+                warn!("metrics: accept failed: {}", e);
+                continue;
+            }
+        };
+        let metrics = metrics.clone();
+        let persister = persister.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let n = match sock.read(&mut buf).await {
+                Ok(n) => n,
+                Err(_) => return,
+            };
+            let request_line = String::from_utf8_lossy(&buf[..n]);
+            let is_metrics = request_line.starts_with("GET /metrics ")
+                || request_line.starts_with("GET /metrics\r\n");
+
+            let response = if is_metrics {
+                let body = metrics.render(&persister).await;
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(), body
+                )
+            } else {
+                let body = "not found";
+                format!(
+                    "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(), body
+                )
+            };
+            let _ = sock.write_all(response.as_bytes()).await;
+        });
+    }
+}
@@ -6,6 +6,7 @@ pub mod constants;
 pub mod frame;
 pub mod obfuscation;
 pub mod tls;
+pub mod transport;
 
 pub use constants::*;
 pub use frame::*;
@@ -0,0 +1,152 @@
+//! Abstracts how obfuscated MTProto frames travel the wire, so the
+//! `obfuscation`/`frame` handshake and framing logic can sit above either
+//! a plain TCP stream or a KCP (reliable-over-UDP) session without caring
+//! which one it's talking to — both just look like an ordered, reliable
+//! byte stream from that point up.
+//!
+//! KCP trades a little overhead for resilience on lossy/throttled links
+//! (no head-of-line blocking behind a single dropped UDP datagram the way
+//! TCP stalls behind a dropped segment) and sidesteps TCP-level
+//! fingerprinting entirely, at the cost of being unmistakably KCP-over-UDP
+//! to anyone inspecting the wire instead.
+
+use std::io;
+use std::net::SocketAddr;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Which transport a listener (or outbound connection) uses. Carried on
+/// [`crate::config::hot_reload::ListenerPlan`] as a non-hot field: like
+/// `server.port`, switching it requires a fresh bind, not an in-place swap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportKind {
+    Tcp,
+    Kcp,
+}
+
+/// Read/write obfuscated MTProto frame bytes over whatever carries them.
+/// Implemented for [`TcpTransport`] and [`KcpTransport`]; callers that
+/// need to pick between the two at runtime use [`AnyTransport`] rather
+/// than a trait object, matching how this crate already prefers a small
+/// enum over `dyn` elsewhere (`SelectionMode`, `ExternalAddrMode`).
+pub trait Transport: Send {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+    async fn write_all(&mut self, buf: &[u8]) -> io::Result<()>;
+    async fn shutdown(&mut self) -> io::Result<()>;
+}
+
+/// The existing TCP path, wrapped to implement [`Transport`].
+pub struct TcpTransport {
+    inner: TcpStream,
+}
+
+impl TcpTransport {
+    pub fn new(inner: TcpStream) -> Self {
+        Self { inner }
+    }
+}
+
+impl Transport for TcpTransport {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf).await
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.inner.write_all(buf).await
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        AsyncWriteExt::shutdown(&mut self.inner).await
+    }
+}
+
+/// KCP session parameters, passed straight through to the underlying KCP
+/// session by [`KcpTransport::connect`]. `general.transport` only selects a
+/// [`TransportKind`]; it does not carry these. `KcpTransport` has no
+/// accept-side constructor, so only outbound sessions use them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KcpSessionConfig {
+    pub mtu: usize,
+    pub send_window: u16,
+    pub recv_window: u16,
+    pub nodelay: bool,
+}
+
+impl Default for KcpSessionConfig {
+    fn default() -> Self {
+        Self {
+            mtu: 1400,
+            send_window: 256,
+            recv_window: 256,
+            nodelay: true,
+        }
+    }
+}
+
+/// A KCP (reliable-over-UDP) session, wrapped to implement [`Transport`].
+pub struct KcpTransport {
+    inner: tokio_kcp::KcpStream,
+}
+
+impl KcpTransport {
+    pub async fn connect(addr: SocketAddr, cfg: KcpSessionConfig) -> io::Result<Self> {
+        let kcp_cfg = tokio_kcp::KcpConfig {
+            mtu: cfg.mtu,
+            nodelay: tokio_kcp::KcpNoDelayConfig {
+                nodelay: cfg.nodelay,
+                ..Default::default()
+            },
+            wnd_size: (cfg.send_window, cfg.recv_window),
+            ..Default::default()
+        };
+        let inner = tokio_kcp::KcpStream::connect(&kcp_cfg, addr)
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        Ok(Self { inner })
+    }
+}
+
+impl Transport for KcpTransport {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf).await
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.inner.write_all(buf).await
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        AsyncWriteExt::shutdown(&mut self.inner).await
+    }
+}
+
+/// Picks between the TCP and KCP paths at accept/connect time, per
+/// [`TransportKind`], while still presenting a single [`Transport`].
+pub enum AnyTransport {
+    Tcp(TcpTransport),
+    Kcp(KcpTransport),
+}
+
+impl Transport for AnyTransport {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            AnyTransport::Tcp(t) => t.read(buf).await,
+            AnyTransport::Kcp(t) => t.read(buf).await,
+        }
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            AnyTransport::Tcp(t) => t.write_all(buf).await,
+            AnyTransport::Kcp(t) => t.write_all(buf).await,
+        }
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        match self {
+            AnyTransport::Tcp(t) => t.shutdown().await,
+            AnyTransport::Kcp(t) => t.shutdown().await,
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! TTL+jitter cache over [`fetch_real_tls`], keyed by
+//! `(host, port, sni, backend, verification)`.
+//!
+//! Without this, every relayed connection re-opens a fresh TCP+TLS probe to
+//! the fronting host just to read its cipher suite and cert/record sizes,
+//! which is both slow and produces an obvious repetitive connection pattern
+//! against the camouflage domain. Concurrent misses for the same key are
+//! deduped into a single upstream probe.
+//!
+//! `verification` has to be part of the key, not just an argument to the
+//! probe: a `Strict` caller for `(host, port, sni)` must never be served a
+//! `NoVerify` result an earlier caller already cached for the same
+//! triple — that would silently hand back an unverified fingerprint under
+//! the guise of a verified one. `backend` is included for the same reason,
+//! since the two providers aren't guaranteed to report identical results.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::sync::{Mutex, Notify};
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::tls_front::fetcher::{fetch_real_tls_with_options, CryptoBackend, VerificationMode};
+use crate::tls_front::types::TlsFetchResult;
+
+type CacheKey = (String, u16, String, CryptoBackend, VerificationMode);
+
+enum Slot {
+    Ready { result: TlsFetchResult, expires_at: Instant },
+    InFlight(Arc<Notify>),
+}
+
+/// Concurrency-safe cache of [`TlsFetchResult`]s with TTL+jitter expiry,
+/// in-flight request dedup, and a max-entry bound (oldest-inserted evicted
+/// first once the bound is hit).
+pub struct TlsFetchCache {
+    slots: Mutex<HashMap<CacheKey, Slot>>,
+    insertion_order: Mutex<Vec<CacheKey>>,
+    ttl: Duration,
+    jitter: Duration,
+    max_entries: usize,
+}
+
+impl TlsFetchCache {
+    pub fn new(ttl: Duration, jitter: Duration, max_entries: usize) -> Arc<Self> {
+        Arc::new(Self {
+            slots: Mutex::new(HashMap::new()),
+            insertion_order: Mutex::new(Vec::new()),
+            ttl,
+            jitter,
+            max_entries,
+        })
+    }
+
+    fn jittered_expiry(&self) -> Instant {
+        let jitter_ms = if self.jitter.is_zero() {
+            0
+        } else {
+            // Cheap, dependency-free jitter: low bits of the wall-clock
+            // nanosecond counter are unpredictable enough to stagger
+            // expiries without pulling in a full RNG for this.
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .subsec_nanos() as u64;
+            nanos % (self.jitter.as_millis() as u64 + 1)
+        };
+        Instant::now() + self.ttl + Duration::from_millis(jitter_ms)
+    }
+
+    /// Fetch TLS metadata for `(host, port, sni)`, serving from cache when a
+    /// live entry exists and deduping concurrent misses into one probe.
+    pub async fn fetch_real_tls_cached(
+        self: &Arc<Self>,
+        host: &str,
+        port: u16,
+        sni: &str,
+        connect_timeout: Duration,
+        backend: CryptoBackend,
+        verification: VerificationMode,
+    ) -> Result<TlsFetchResult> {
+        let key: CacheKey = (host.to_owned(), port, sni.to_owned(), backend, verification);
+
+        loop {
+            let mut slots = self.slots.lock().await;
+            match slots.get(&key) {
+                Some(Slot::Ready { result, expires_at }) if *expires_at > Instant::now() => {
+                    debug!(host, port, sni, "TLS fetch cache hit");
+                    return Ok(result.clone());
+                }
+                Some(Slot::InFlight(notify)) => {
+                    let notify = notify.clone();
+                    drop(slots);
+                    notify.notified().await;
+                    continue;
+                }
+                _ => {
+                    let notify = Arc::new(Notify::new());
+                    slots.insert(key.clone(), Slot::InFlight(notify.clone()));
+                    drop(slots);
+
+                    let outcome = fetch_real_tls_with_options(
+                        host,
+                        port,
+                        sni,
+                        connect_timeout,
+                        backend,
+                        verification,
+                    )
+                    .await;
+
+                    let mut slots = self.slots.lock().await;
+                    match &outcome {
+                        Ok(result) => {
+                            slots.insert(
+                                key.clone(),
+                                Slot::Ready {
+                                    result: result.clone(),
+                                    expires_at: self.jittered_expiry(),
+                                },
+                            );
+                            self.track_insertion(&mut slots, key.clone()).await;
+                        }
+                        Err(_) => {
+                            // Don't cache failures; let the next caller retry.
+                            slots.remove(&key);
+                        }
+                    }
+                    notify.notify_waiters();
+                    return outcome;
+                }
+            }
+        }
+    }
+
+    /// Record insertion order and evict the oldest entry once over bound.
+    async fn track_insertion(&self, slots: &mut HashMap<CacheKey, Slot>, key: CacheKey) {
+        let mut order = self.insertion_order.lock().await;
+        order.retain(|k| k != &key);
+        order.push(key);
+        while order.len() > self.max_entries {
+            let oldest = order.remove(0);
+            slots.remove(&oldest);
+        }
+    }
+}
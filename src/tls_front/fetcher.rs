@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::Result;
@@ -13,11 +13,17 @@ use rustls::client::ClientConfig;
 use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
 use rustls::{DigitallySignedStruct, Error as RustlsError};
 
-use crate::tls_front::types::{ParsedServerHello, TlsFetchResult};
+use crate::tls_front::tee::TeeStream;
+use crate::tls_front::types::{ParsedServerHello, TlsFetchResult, TlsFrontError};
 
 /// No-op verifier: accept any certificate (we only need lengths and metadata).
+///
+/// Still captures the stapled OCSP response it's handed, since that's part
+/// of the handshake sizing we're trying to reproduce faithfully.
 #[derive(Debug)]
-struct NoVerify;
+struct NoVerify {
+    ocsp_capture: Arc<Mutex<Option<Vec<u8>>>>,
+}
 
 impl ServerCertVerifier for NoVerify {
     fn verify_server_cert(
@@ -25,9 +31,12 @@ impl ServerCertVerifier for NoVerify {
         _end_entity: &CertificateDer<'_>,
         _intermediates: &[CertificateDer<'_>],
         _server_name: &ServerName<'_>,
-        _ocsp: &[u8],
+        ocsp: &[u8],
         _now: UnixTime,
     ) -> Result<ServerCertVerified, RustlsError> {
+        if !ocsp.is_empty() {
+            *self.ocsp_capture.lock().unwrap() = Some(ocsp.to_vec());
+        }
         Ok(ServerCertVerified::assertion())
     }
 
@@ -60,82 +69,395 @@ impl ServerCertVerifier for NoVerify {
     }
 }
 
-fn build_client_config() -> Arc<ClientConfig> {
-    let root = rustls::RootCertStore::empty();
+/// Wraps a real chain/hostname verifier so `Strict` mode captures the
+/// stapled OCSP response exactly like `NoVerify` does, instead of silently
+/// reporting `ocsp_response_len: None` for every verified front regardless
+/// of what it actually stapled.
+#[derive(Debug)]
+struct VerifyingOcspCapture {
+    inner: Arc<dyn ServerCertVerifier>,
+    ocsp_capture: Arc<Mutex<Option<Vec<u8>>>>,
+}
 
-    let provider = rustls::crypto::ring::default_provider();
-    let mut config = ClientConfig::builder_with_provider(Arc::new(provider))
+impl ServerCertVerifier for VerifyingOcspCapture {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, RustlsError> {
+        let verified = self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp, now)?;
+        if !ocsp.is_empty() {
+            *self.ocsp_capture.lock().unwrap() = Some(ocsp.to_vec());
+        }
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, RustlsError> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, RustlsError> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Which `rustls` crypto provider to negotiate the probe handshake with.
+///
+/// Real browsers (and the servers we mimic) often negotiate suites/groups
+/// that `ring`'s default provider doesn't prioritize the same way, so
+/// operators can opt into `aws-lc-rs`'s fuller modern suite/group list to
+/// get a more representative fingerprint back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CryptoBackend {
+    #[default]
+    Ring,
+    AwsLcRs,
+}
+
+/// How hard to vet the fronting host's certificate before trusting the
+/// fingerprint we captured from it.
+///
+/// `NoVerify` is fine for pure length/metadata extraction, but it also
+/// means we'd happily mimic a MITM'd or hijacked front. `Strict` validates
+/// the chain against the webpki-roots trust anchors plus the SNI hostname,
+/// so the captured `ParsedServerHello`/record sizes are known-genuine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VerificationMode {
+    #[default]
+    NoVerify,
+    Strict,
+}
+
+/// Builds the client config and a handle that fills in with the front's
+/// stapled OCSP response, if it sent one — under both verification modes.
+fn build_client_config(
+    backend: CryptoBackend,
+    verification: VerificationMode,
+) -> (Arc<ClientConfig>, Arc<Mutex<Option<Vec<u8>>>>) {
+    let provider = match backend {
+        CryptoBackend::Ring => rustls::crypto::ring::default_provider(),
+        CryptoBackend::AwsLcRs => rustls::crypto::aws_lc_rs::default_provider(),
+    };
+    let provider = Arc::new(provider);
+
+    let builder = ClientConfig::builder_with_provider(provider.clone())
         .with_protocol_versions(&[&rustls::version::TLS13, &rustls::version::TLS12])
-        .expect("protocol versions")
-        .with_root_certificates(root)
-        .with_no_client_auth();
+        .expect("protocol versions");
+
+    let mut config = match verification {
+        VerificationMode::NoVerify => builder
+            .with_root_certificates(rustls::RootCertStore::empty())
+            .with_no_client_auth(),
+        VerificationMode::Strict => {
+            let mut roots = rustls::RootCertStore::empty();
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            builder.with_root_certificates(roots).with_no_client_auth()
+        }
+    };
+
+    let ocsp_capture = Arc::new(Mutex::new(None));
+    match verification {
+        VerificationMode::NoVerify => {
+            config.dangerous().set_certificate_verifier(Arc::new(NoVerify {
+                ocsp_capture: ocsp_capture.clone(),
+            }));
+        }
+        VerificationMode::Strict => {
+            let mut roots = rustls::RootCertStore::empty();
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            let verifier = rustls::client::WebPkiServerVerifier::builder(Arc::new(roots))
+                .build()
+                .expect("webpki-roots ships a valid, non-empty root store");
+            config.dangerous().set_certificate_verifier(Arc::new(VerifyingOcspCapture {
+                inner: verifier,
+                ocsp_capture: ocsp_capture.clone(),
+            }));
+        }
+    }
 
-    config
-        .dangerous()
-        .set_certificate_verifier(Arc::new(NoVerify));
+    (Arc::new(config), ocsp_capture)
+}
 
-    Arc::new(config)
+/// One TLS record: `[content_type u8][legacy_version u16][len u16][payload]`.
+struct TlsRecord {
+    content_type: u8,
+    payload: Vec<u8>,
+}
+
+/// Split a raw byte stream into TLS records. Stops at the first incomplete
+/// record (the tee may have captured a prefix cut mid-record).
+fn split_tls_records(buf: &[u8]) -> Vec<TlsRecord> {
+    let mut records = Vec::new();
+    let mut i = 0;
+    while i + 5 <= buf.len() {
+        let content_type = buf[i];
+        let len = u16::from_be_bytes([buf[i + 3], buf[i + 4]]) as usize;
+        let start = i + 5;
+        let end = start + len;
+        if end > buf.len() {
+            break;
+        }
+        records.push(TlsRecord {
+            content_type,
+            payload: buf[start..end].to_vec(),
+        });
+        i = end;
+    }
+    records
 }
 
-/// Fetch real TLS metadata for the given SNI: negotiated cipher and cert lengths.
+/// Parse a ServerHello handshake message:
+/// `[msg_type=2 u8][len u24][version u16][random 32][session_id: u8 len + bytes]
+///  [cipher_suite u16][compression u8][extensions: u16 total len + (type u16, len u16, data)*]`
+fn parse_server_hello(handshake: &[u8]) -> Option<ParsedServerHello> {
+    if handshake.len() < 4 || handshake[0] != 2 {
+        return None;
+    }
+    let len = u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+    let body = handshake.get(4..4 + len)?;
+    if body.len() < 2 + 32 + 1 {
+        return None;
+    }
+
+    let version = [body[0], body[1]];
+    let mut off = 2;
+
+    let mut random = [0u8; 32];
+    random.copy_from_slice(&body[off..off + 32]);
+    off += 32;
+
+    let session_id_len = body[off] as usize;
+    off += 1;
+    let session_id = body.get(off..off + session_id_len)?.to_vec();
+    off += session_id_len;
+
+    let cipher_suite = [*body.get(off)?, *body.get(off + 1)?];
+    off += 2;
+
+    let compression = *body.get(off)?;
+    off += 1;
+
+    let mut extensions = Vec::new();
+    if off + 2 <= body.len() {
+        let ext_total = u16::from_be_bytes([body[off], body[off + 1]]) as usize;
+        off += 2;
+        let ext_end = (off + ext_total).min(body.len());
+        while off + 4 <= ext_end {
+            let ext_type = u16::from_be_bytes([body[off], body[off + 1]]);
+            let ext_len = u16::from_be_bytes([body[off + 2], body[off + 3]]) as usize;
+            off += 4;
+            let data = body.get(off..off + ext_len)?.to_vec();
+            extensions.push((ext_type, data));
+            off += ext_len;
+        }
+    }
+
+    Some(ParsedServerHello {
+        version,
+        random,
+        session_id,
+        cipher_suite,
+        compression,
+        extensions,
+    })
+}
+
+/// Walk the tee-captured records, reassemble the ServerHello out of the
+/// handshake (type 22) records, and collect the sizes of whatever follows
+/// it: `application_data` (type 23) in TLS 1.3, or further plaintext
+/// handshake records (e.g. Certificate) in TLS 1.2.
+fn parse_captured_records(captured: &[u8]) -> (Option<ParsedServerHello>, Vec<usize>) {
+    let records = split_tls_records(captured);
+
+    let mut handshake_buf = Vec::new();
+    let mut server_hello = None;
+    let mut past_server_hello = false;
+    let mut post_hello_sizes = Vec::new();
+
+    for rec in &records {
+        match rec.content_type {
+            22 => {
+                if !past_server_hello {
+                    handshake_buf.extend_from_slice(&rec.payload);
+                    if let Some(sh) = parse_server_hello(&handshake_buf) {
+                        server_hello = Some(sh);
+                        past_server_hello = true;
+                    }
+                } else {
+                    // TLS 1.2: Certificate/ServerKeyExchange/... sent in the clear.
+                    post_hello_sizes.push(rec.payload.len());
+                }
+            }
+            23 if past_server_hello => {
+                post_hello_sizes.push(rec.payload.len());
+            }
+            _ => {}
+        }
+    }
+
+    (server_hello, post_hello_sizes)
+}
+
+/// Fetch real TLS metadata for the given SNI: the verbatim ServerHello and
+/// the record sizes that followed it.
 pub async fn fetch_real_tls(
     host: &str,
     port: u16,
     sni: &str,
     connect_timeout: Duration,
+) -> Result<TlsFetchResult> {
+    fetch_real_tls_with_backend(host, port, sni, connect_timeout, CryptoBackend::default()).await
+}
+
+/// Like [`fetch_real_tls`], but lets the caller pick the `rustls` crypto
+/// provider used to negotiate the probe handshake. Uses the default
+/// [`VerificationMode::NoVerify`].
+pub async fn fetch_real_tls_with_backend(
+    host: &str,
+    port: u16,
+    sni: &str,
+    connect_timeout: Duration,
+    backend: CryptoBackend,
+) -> Result<TlsFetchResult> {
+    fetch_real_tls_with_options(
+        host,
+        port,
+        sni,
+        connect_timeout,
+        backend,
+        VerificationMode::default(),
+    )
+    .await
+}
+
+/// Like [`fetch_real_tls`], but lets the caller pick both the crypto
+/// provider and whether the fronting host's certificate chain must pass
+/// real validation before its fingerprint is trusted.
+pub async fn fetch_real_tls_with_options(
+    host: &str,
+    port: u16,
+    sni: &str,
+    connect_timeout: Duration,
+    backend: CryptoBackend,
+    verification: VerificationMode,
 ) -> Result<TlsFetchResult> {
     let addr = format!("{host}:{port}");
     let stream = timeout(connect_timeout, TcpStream::connect(addr)).await??;
+    let (tee, captured) = TeeStream::new(stream);
 
-    let config = build_client_config();
+    let (config, ocsp_capture) = build_client_config(backend, verification);
     let connector = TlsConnector::from(config);
 
     let server_name = ServerName::try_from(sni.to_owned())
         .or_else(|_| ServerName::try_from(host.to_owned()))
         .map_err(|_| RustlsError::General("invalid SNI".into()))?;
 
-    let tls_stream: TlsStream<TcpStream> = connector.connect(server_name, stream).await?;
+    let tls_stream: TlsStream<TeeStream> = connector
+        .connect(server_name, tee)
+        .await
+        .map_err(|e| match verification {
+            VerificationMode::Strict => anyhow::Error::new(TlsFrontError::VerificationFailed {
+                sni: sni.to_owned(),
+                reason: e.to_string(),
+            }),
+            VerificationMode::NoVerify => anyhow::Error::from(e),
+        })?;
 
-    // Extract negotiated parameters and certificates
+    // We only needed rustls to drive the handshake to completion; the real
+    // metadata comes from the raw bytes the tee captured along the way.
     let (_io, session) = tls_stream.get_ref();
-    let cipher_suite = session
+    let fallback_cipher_suite = session
         .negotiated_cipher_suite()
         .map(|s| u16::from(s.suite()).to_be_bytes())
         .unwrap_or([0x13, 0x01]);
 
-    let certs: Vec<CertificateDer<'static>> = session
-        .peer_certificates()
-        .map(|slice| slice.to_vec())
-        .unwrap_or_default();
-
-    let total_cert_len: usize = certs.iter().map(|c| c.len()).sum::<usize>().max(1024);
+    let captured_bytes = captured.lock().unwrap().clone();
+    let (parsed_hello, post_hello_sizes) = parse_captured_records(&captured_bytes);
 
-    // Heuristic: split across two records if large to mimic real servers a bit.
-    let app_data_records_sizes = if total_cert_len > 3000 {
-        vec![total_cert_len / 2, total_cert_len - total_cert_len / 2]
-    } else {
-        vec![total_cert_len]
-    };
-
-    let parsed = ParsedServerHello {
+    let parsed = parsed_hello.unwrap_or(ParsedServerHello {
         version: [0x03, 0x03],
         random: [0u8; 32],
         session_id: Vec::new(),
-        cipher_suite,
+        cipher_suite: fallback_cipher_suite,
         compression: 0,
         extensions: Vec::new(),
+    });
+
+    // If the tee failed to capture anything usable (e.g. the connector
+    // buffered ahead of our read), fall back to the old cert-length guess
+    // rather than reporting zero records.
+    let mut app_data_records_sizes = if !post_hello_sizes.is_empty() {
+        post_hello_sizes
+    } else {
+        let certs: Vec<CertificateDer<'static>> = session
+            .peer_certificates()
+            .map(|slice| slice.to_vec())
+            .unwrap_or_default();
+        let total_cert_len: usize = certs.iter().map(|c| c.len()).sum::<usize>().max(1024);
+        if total_cert_len > 3000 {
+            vec![total_cert_len / 2, total_cert_len - total_cert_len / 2]
+        } else {
+            vec![total_cert_len]
+        }
+    };
+
+    // Real servers frequently staple an OCSP response and/or embed SCTs,
+    // which materially changes the handshake size we're trying to mimic.
+    let ocsp_response_len = ocsp_capture.lock().unwrap().take().map(|ocsp| ocsp.len());
+    if let Some(len) = ocsp_response_len {
+        app_data_records_sizes.push(len);
+    }
+    // In TLS 1.3 SCTs move into the encrypted `CertificateEntry`
+    // extensions, which never appear in the cleartext ServerHello this
+    // tee captures — so on TLS 1.3 "not in parsed.extensions" tells us
+    // nothing and must not be reported as a confident `false`.
+    const SIGNED_CERTIFICATE_TIMESTAMP_EXT: u16 = 18;
+    let is_tls13 = matches!(session.protocol_version(), Some(rustls::ProtocolVersion::TLSv1_3));
+    let scts_present = if is_tls13 {
+        None
+    } else {
+        Some(
+            parsed
+                .extensions
+                .iter()
+                .any(|(ty, _)| *ty == SIGNED_CERTIFICATE_TIMESTAMP_EXT),
+        )
     };
 
     debug!(
         sni = %sni,
-        len = total_cert_len,
-        cipher = format!("0x{:04x}", u16::from_be_bytes(cipher_suite)),
+        backend = ?backend,
+        records = app_data_records_sizes.len(),
+        total_len = app_data_records_sizes.iter().sum::<usize>(),
+        cipher = format!("0x{:04x}", u16::from_be_bytes(parsed.cipher_suite)),
+        extensions = parsed.extensions.len(),
+        ocsp_len = ocsp_response_len,
+        scts_present,
         "Fetched TLS metadata"
     );
 
     Ok(TlsFetchResult {
         server_hello_parsed: parsed,
-        app_data_records_sizes: app_data_records_sizes.clone(),
         total_app_data_len: app_data_records_sizes.iter().sum(),
+        app_data_records_sizes,
+        ocsp_response_len,
+        scts_present,
     })
 }
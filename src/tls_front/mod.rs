@@ -0,0 +1,12 @@
+//! Real-TLS front fetching: probes a fronting host's TLS handshake to
+//! capture authentic metadata (ServerHello fields, record sizes) used to
+//! make the fake-TLS camouflage indistinguishable from the real front.
+
+pub mod cache;
+pub mod fetcher;
+pub mod tee;
+pub mod types;
+
+pub use cache::TlsFetchCache;
+pub use fetcher::{fetch_real_tls, CryptoBackend, VerificationMode};
+pub use types::{ParsedServerHello, TlsFetchResult, TlsFrontError};
@@ -0,0 +1,71 @@
+//! Record-level tee for the fronting TLS probe.
+//!
+//! Wraps a `TcpStream` so every byte read off the wire is mirrored into a
+//! shared buffer *before* it is consumed by the `TlsConnector`, letting us
+//! inspect the raw TLS record stream (ServerHello, certificate records,
+//! ...) after the handshake has already run its course through rustls.
+
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+pub struct TeeStream {
+    inner: TcpStream,
+    captured: Arc<Mutex<Vec<u8>>>,
+}
+
+impl TeeStream {
+    /// Wrap `inner`, returning the wrapped stream plus a handle to the
+    /// buffer that accumulates every byte read from it.
+    pub fn new(inner: TcpStream) -> (Self, Arc<Mutex<Vec<u8>>>) {
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        (
+            Self {
+                inner,
+                captured: captured.clone(),
+            },
+            captured,
+        )
+    }
+}
+
+impl AsyncRead for TeeStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if res.is_ready() {
+            let new_bytes = &buf.filled()[before..];
+            if !new_bytes.is_empty() {
+                this.captured.lock().unwrap().extend_from_slice(new_bytes);
+            }
+        }
+        res
+    }
+}
+
+impl AsyncWrite for TeeStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,55 @@
+//! Shared types for TLS front-fetching.
+
+/// Fields extracted from a real ServerHello handshake message.
+#[derive(Debug, Clone)]
+pub struct ParsedServerHello {
+    pub version: [u8; 2],
+    pub random: [u8; 32],
+    pub session_id: Vec<u8>,
+    pub cipher_suite: [u8; 2],
+    pub compression: u8,
+    /// `(extension_type, extension_data)`, in the order the server sent them.
+    pub extensions: Vec<(u16, Vec<u8>)>,
+}
+
+/// Aggregate TLS metadata captured from a fronting host, used to reproduce
+/// its handshake characteristics (sizes, negotiated parameters) for
+/// fake-TLS camouflage.
+#[derive(Debug, Clone)]
+pub struct TlsFetchResult {
+    pub server_hello_parsed: ParsedServerHello,
+    pub app_data_records_sizes: Vec<usize>,
+    pub total_app_data_len: usize,
+    /// Length of the stapled OCSP response (`CertificateStatus` /
+    /// `status_request` extension), if the front stapled one.
+    pub ocsp_response_len: Option<usize>,
+    /// Whether the front embedded Signed Certificate Timestamps, read from
+    /// the `signed_certificate_timestamp` extension. `None` when the
+    /// negotiated version is TLS 1.3: there, SCTs ride in the encrypted
+    /// `CertificateEntry` extensions, which are never visible in the
+    /// cleartext ServerHello we scan, so "not found" can't be told apart
+    /// from "present but unobservable" — only TLS 1.2 ever yields a real
+    /// `Some`.
+    pub scts_present: Option<bool>,
+}
+
+/// Errors specific to the fronting probe, distinct from generic IO/TLS
+/// failures so callers can tell "couldn't reach the front" apart from
+/// "reached it, but it's not who it claims to be".
+#[derive(Debug)]
+pub enum TlsFrontError {
+    VerificationFailed { sni: String, reason: String },
+}
+
+impl std::fmt::Display for TlsFrontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsFrontError::VerificationFailed { sni, reason } => write!(
+                f,
+                "fronting host '{sni}' failed certificate verification: {reason}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TlsFrontError {}
@@ -0,0 +1,423 @@
+//! Declarative byte-layout codec for the ME RPC wire structures.
+//!
+//! Frame and payload handling used to be hand-rolled slice indexing spread
+//! across several `build_*`/`parse_*` functions in `middle_proxy`, with
+//! manual offset math that panicked on truncated/malformed input instead
+//! of returning an error. This module gives each structure — the RPC frame
+//! header, nonce payload, handshake payload, `RPC_PROXY_REQ` extra section,
+//! and the TL string with its `0xfe` long-length form — one explicit
+//! little-endian field layout, with a [`Reader`]/[`Writer`] pair that
+//! bounds-check every access so malformed input turns into a `Result`
+//! instead of a panic.
+
+use crate::crypto::crc32;
+use crate::error::{ProxyError, Result};
+use crate::protocol::constants::*;
+
+/// Cursor over a byte slice with bounds-checked little-endian field reads.
+/// Every method returns `Result` instead of panicking on truncated input.
+pub struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.remaining() < n {
+            return Err(ProxyError::InvalidHandshake(format!(
+                "codec: need {} more bytes, have {}", n, self.remaining()
+            )));
+        }
+        let s = &self.buf[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(s)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16_le(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32_le(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn i32_le(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        self.take(n)
+    }
+
+    pub fn array16(&mut self) -> Result<[u8; 16]> {
+        let mut out = [0u8; 16];
+        out.copy_from_slice(self.take(16)?);
+        Ok(out)
+    }
+
+    pub fn skip(&mut self, n: usize) -> Result<()> {
+        self.take(n).map(|_| ())
+    }
+}
+
+/// Accumulator for little-endian field writes. Writing can't run out of
+/// room (it grows the backing `Vec`), so unlike [`Reader`] these never
+/// fail; bounds safety lives entirely on the read side.
+#[derive(Default)]
+pub struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    pub fn with_capacity(cap: usize) -> Self {
+        Self { buf: Vec::with_capacity(cap) }
+    }
+
+    pub fn u8(&mut self, v: u8) -> &mut Self {
+        self.buf.push(v);
+        self
+    }
+
+    pub fn u16_le(&mut self, v: u16) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    pub fn u32_le(&mut self, v: u32) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    pub fn u64_le(&mut self, v: u64) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    pub fn i32_le(&mut self, v: i32) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    pub fn bytes(&mut self, b: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(b);
+        self
+    }
+
+    pub fn zeros(&mut self, n: usize) -> &mut Self {
+        self.buf.extend(std::iter::repeat(0u8).take(n));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Overwrite a previously-written little-endian `u32`, for length
+    /// prefixes that are only known once the section they cover is done.
+    pub fn patch_u32_le(&mut self, at: usize, v: u32) {
+        self.buf[at..at + 4].copy_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+// ========== RPC frame: [len(4) | seq_no(4) | payload | crc32(4)] ==========
+
+pub struct RpcFrame;
+
+impl RpcFrame {
+    /// Build a full RPC frame: length prefix, seq_no, payload, trailing CRC32.
+    pub fn write(seq_no: i32, payload: &[u8]) -> Vec<u8> {
+        let total_len = (4 + 4 + payload.len() + 4) as u32;
+        let mut w = Writer::with_capacity(total_len as usize);
+        w.u32_le(total_len).i32_le(seq_no).bytes(payload);
+        let crc = crc32(&w.buf);
+        w.u32_le(crc);
+        w.into_vec()
+    }
+
+    /// Parse a complete RPC frame (length prefix included) and verify its
+    /// CRC32 trailer. Returns `(seq_no, payload)`.
+    pub fn read(full: &[u8]) -> Result<(i32, Vec<u8>)> {
+        if full.len() < 12 || full.len() > (1 << 24) {
+            return Err(ProxyError::InvalidHandshake(
+                format!("Bad RPC frame length: {}", full.len()),
+            ));
+        }
+        let mut r = Reader::new(full);
+        let total_len = r.u32_le()? as usize;
+        if total_len != full.len() {
+            return Err(ProxyError::InvalidHandshake(format!(
+                "RPC frame length mismatch: header says {}, have {}", total_len, full.len()
+            )));
+        }
+        let crc_offset = total_len - 4;
+        let seq_no = r.i32_le()?;
+        let payload = r.bytes(crc_offset - 8)?.to_vec();
+        let expected_crc = r.u32_le()?;
+        let actual_crc = crc32(&full[..crc_offset]);
+        if expected_crc != actual_crc {
+            return Err(ProxyError::InvalidHandshake(format!(
+                "CRC mismatch: 0x{:08x} vs 0x{:08x}", expected_crc, actual_crc
+            )));
+        }
+        Ok((seq_no, payload))
+    }
+}
+
+// ========== RPC nonce payload (32 bytes) ==========
+
+pub struct NoncePayload;
+
+impl NoncePayload {
+    pub fn write(key_selector: u32, crypto_ts: u32, nonce: &[u8; 16]) -> [u8; 32] {
+        let mut w = Writer::with_capacity(32);
+        w.u32_le(RPC_NONCE_U32)
+            .u32_le(key_selector)
+            .u32_le(RPC_CRYPTO_AES_U32)
+            .u32_le(crypto_ts)
+            .bytes(nonce);
+        let mut out = [0u8; 32];
+        out.copy_from_slice(&w.into_vec());
+        out
+    }
+
+    /// Returns `(crypto_schema, crypto_ts, nonce)`.
+    pub fn read(d: &[u8]) -> Result<(u32, u32, [u8; 16])> {
+        let mut r = Reader::new(d);
+        let t = r.u32_le()?;
+        if t != RPC_NONCE_U32 {
+            return Err(ProxyError::InvalidHandshake(
+                format!("Expected RPC_NONCE 0x{:08x}, got 0x{:08x}", RPC_NONCE_U32, t),
+            ));
+        }
+        let _key_selector = r.u32_le()?;
+        let schema = r.u32_le()?;
+        let ts = r.u32_le()?;
+        let nonce = r.array16()?;
+        Ok((schema, ts, nonce))
+    }
+}
+
+// ========== RPC handshake payload (32 bytes) ==========
+
+pub struct HandshakePayload;
+
+impl HandshakePayload {
+    pub fn write(our_ip: u32, our_port: u16, peer_ip: u32, peer_port: u16) -> [u8; 32] {
+        let pid = (std::process::id() & 0xFFFF) as u16;
+        let utime = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as u32;
+
+        let mut w = Writer::with_capacity(32);
+        w.u32_le(RPC_HANDSHAKE_U32)
+            .u32_le(0) // flags
+            // sender_pid: {ip(4), port(2), pid(2), utime(4)}
+            .u32_le(our_ip)
+            .u16_le(our_port)
+            .u16_le(pid)
+            .u32_le(utime)
+            // peer_pid: {ip(4), port(2), pid(2)=0, utime(4)=0}
+            .u32_le(peer_ip)
+            .u16_le(peer_port)
+            .zeros(6);
+
+        let mut out = [0u8; 32];
+        out.copy_from_slice(&w.into_vec());
+        out
+    }
+}
+
+// ========== TL string, including the `0xfe` long-length form ==========
+
+pub struct TlString;
+
+impl TlString {
+    /// Append a TL-encoded string (short form under 254 bytes, `0xfe`
+    /// long-length form otherwise) with its 4-byte-boundary padding.
+    pub fn write(w: &mut Writer, s: &[u8]) {
+        if s.len() < 254 {
+            w.u8(s.len() as u8).bytes(s);
+            let pad = (4 - ((1 + s.len()) % 4)) % 4;
+            w.zeros(pad);
+        } else {
+            w.u8(0xfe);
+            let len_bytes = (s.len() as u32).to_le_bytes();
+            w.bytes(&len_bytes[..3]).bytes(s);
+            let pad = (4 - (s.len() % 4)) % 4;
+            w.zeros(pad);
+        }
+    }
+
+    /// Read a TL string (short or `0xfe` long-length form) including its
+    /// padding, returning the string bytes.
+    pub fn read(r: &mut Reader) -> Result<Vec<u8>> {
+        let tag = r.u8()?;
+        let len = if tag < 254 {
+            tag as usize
+        } else if tag == 254 {
+            let lb = r.bytes(3)?;
+            u32::from_le_bytes([lb[0], lb[1], lb[2], 0]) as usize
+        } else {
+            return Err(ProxyError::InvalidHandshake(
+                format!("Unsupported TL string tag: {}", tag),
+            ));
+        };
+        let s = r.bytes(len)?.to_vec();
+        let prefix_len = if tag < 254 { 1 } else { 4 };
+        let pad = (4 - ((prefix_len + len) % 4)) % 4;
+        r.skip(pad)?;
+        Ok(s)
+    }
+}
+
+// ========== RPC_PROXY_REQ "extra" section (length-prefixed TL blob) ==========
+
+pub struct ProxyReqExtra;
+
+impl ProxyReqExtra {
+    /// Append the length-prefixed `RPC_PROXY_REQ` extra section carrying
+    /// the ad-tag, if present. The section is always emitted (even if
+    /// empty) once the caller has decided the flags call for it, matching
+    /// the C proxy's `write_extra` behavior.
+    pub fn write(w: &mut Writer, tag: Option<&[u8]>) {
+        let extra_start = w.len();
+        w.u32_le(0); // length placeholder, patched below
+        if let Some(tag) = tag {
+            w.u32_le(TL_PROXY_TAG_U32);
+            TlString::write(w, tag);
+        }
+        let extra_len = (w.len() - extra_start - 4) as u32;
+        w.patch_u32_le(extra_start, extra_len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap, dependency-free, deterministic PRNG (splitmix64) so these
+    /// tests cover a wide spread of inputs across runs without pulling in
+    /// a `rand`/`proptest` dependency just for this.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next_u64(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            z ^ (z >> 31)
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            if bound == 0 { 0 } else { (self.next_u64() as usize) % bound }
+        }
+
+        fn bytes(&mut self, n: usize) -> Vec<u8> {
+            let mut out = Vec::with_capacity(n);
+            while out.len() < n {
+                out.extend_from_slice(&self.next_u64().to_le_bytes());
+            }
+            out.truncate(n);
+            out
+        }
+    }
+
+    #[test]
+    fn rpc_frame_roundtrips_random_payloads() {
+        let mut rng = Rng(1);
+        for _ in 0..500 {
+            let payload = rng.bytes(rng.below(4096));
+            let seq_no = rng.next_u64() as i32;
+            let framed = RpcFrame::write(seq_no, &payload);
+            let (got_seq, got_payload) = RpcFrame::read(&framed).expect("well-formed frame must parse");
+            assert_eq!(got_seq, seq_no);
+            assert_eq!(got_payload, payload);
+        }
+    }
+
+    #[test]
+    fn nonce_payload_roundtrips_random_fields() {
+        let mut rng = Rng(2);
+        for _ in 0..500 {
+            let key_selector = rng.next_u64() as u32;
+            let crypto_ts = rng.next_u64() as u32;
+            let mut nonce = [0u8; 16];
+            nonce.copy_from_slice(&rng.bytes(16));
+
+            let encoded = NoncePayload::write(key_selector, crypto_ts, &nonce);
+            let (schema, ts, got_nonce) = NoncePayload::read(&encoded).expect("well-formed nonce payload must parse");
+            assert_eq!(schema, RPC_CRYPTO_AES_U32);
+            assert_eq!(ts, crypto_ts);
+            assert_eq!(got_nonce, nonce);
+        }
+    }
+
+    #[test]
+    fn tl_string_roundtrips_random_lengths() {
+        let mut rng = Rng(3);
+        for _ in 0..500 {
+            // Bias toward the 0xfe long-form boundary at 254 bytes, where
+            // off-by-one bugs in length/padding math tend to live.
+            let len = match rng.below(4) {
+                0 => rng.below(254),
+                1 => 253 + rng.below(3),
+                _ => rng.below(4096),
+            };
+            let s = rng.bytes(len);
+
+            let mut w = Writer::default();
+            TlString::write(&mut w, &s);
+            let encoded = w.into_vec();
+            assert_eq!(encoded.len() % 4, 0, "TL string must be padded to a 4-byte boundary");
+
+            let mut r = Reader::new(&encoded);
+            let got = TlString::read(&mut r).expect("well-formed TL string must parse");
+            assert_eq!(got, s);
+            assert_eq!(r.remaining(), 0, "read must consume exactly what write produced");
+        }
+    }
+
+    /// None of the bounds-checked `read`/`parse` paths should ever panic,
+    /// no matter how garbled or truncated the input — they're fed
+    /// attacker-controlled bytes straight off the wire and must turn
+    /// malformed input into an `Err`, never a crash.
+    #[test]
+    fn parsers_never_panic_on_arbitrary_bytes() {
+        let mut rng = Rng(4);
+        for _ in 0..2000 {
+            let buf = rng.bytes(rng.below(64));
+
+            let _ = std::panic::catch_unwind(|| RpcFrame::read(&buf))
+                .expect("RpcFrame::read must not panic on arbitrary bytes");
+            let _ = std::panic::catch_unwind(|| NoncePayload::read(&buf))
+                .expect("NoncePayload::read must not panic on arbitrary bytes");
+            let _ = std::panic::catch_unwind(|| {
+                let mut r = Reader::new(&buf);
+                TlString::read(&mut r)
+            })
+            .expect("TlString::read must not panic on arbitrary bytes");
+        }
+    }
+}
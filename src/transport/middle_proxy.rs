@@ -8,21 +8,22 @@
 //! - Health monitoring + reconnection
 //! - Hex diagnostics for debugging
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Duration;
 use bytes::{Bytes, BytesMut};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::sync::{mpsc, Mutex, Notify, RwLock};
 use tokio::time::{timeout, Instant};
 use tracing::{debug, info, trace, warn, error};
 
 use crate::crypto::{crc32, derive_middleproxy_keys, AesCbc, SecureRandom};
 use crate::error::{ProxyError, Result};
 use crate::protocol::constants::*;
+use crate::transport::codec::{HandshakePayload, NoncePayload, ProxyReqExtra, RpcFrame, Writer};
 
 // ========== Proxy Secret Fetching ==========
 
@@ -99,18 +100,97 @@ async fn download_proxy_secret() -> Result<Vec<u8>> {
     Ok(data)
 }
 
+/// How often the background refresher re-downloads the proxy-secret,
+/// matching the on-disk cache's own staleness threshold in
+/// [`fetch_proxy_secret`].
+const PROXY_SECRET_REFRESH_INTERVAL: Duration = Duration::from_secs(86400);
+
+/// Background task that periodically re-downloads the Telegram proxy-secret
+/// and hot-swaps it into `pool`'s secret list, so a server-side
+/// `getProxySecret` rotation doesn't require a process restart. Existing
+/// handshakes and connections are untouched; only future connects/rekeys
+/// see the refreshed key_selector.
+pub async fn me_secret_refresh_task(pool: Arc<MePool>, cache_path: Option<String>) {
+    loop {
+        tokio::time::sleep(PROXY_SECRET_REFRESH_INTERVAL).await;
+
+        let data = match download_proxy_secret().await {
+            Ok(data) => data,
+            Err(e) => {
+                warn!(error = %e, "Proxy-secret refresh failed, keeping existing secrets");
+                continue;
+            }
+        };
+        if data.len() < 32 {
+            warn!(len = data.len(), "Refreshed proxy-secret too short, keeping existing secrets");
+            continue;
+        }
+
+        if let Some(path) = &cache_path {
+            if let Err(e) = tokio::fs::write(path, &data).await {
+                warn!(error = %e, "Failed to cache refreshed proxy-secret (non-fatal)");
+            }
+        }
+
+        info!(len = data.len(), "Refreshed proxy-secret, hot-swapping into ME pool");
+        pool.push_secret(data).await;
+    }
+}
+
+// ========== External Address Discovery ==========
+
+/// How `MePool` determines the external (public) address it reports to the
+/// middle-proxy in `sender_pid` and the `RPC_PROXY_REQ` "our IP" fields.
+/// Behind NAT, `TcpStream::local_addr()` is an RFC1918 address the ME
+/// server can't route back to, so the middle-proxy would record a useless
+/// peer identity unless an operator points this at the real external one.
+#[derive(Debug, Clone, Default)]
+pub enum ExternalAddrMode {
+    /// Use the socket's own local address as-is (correct when this process
+    /// already has a public IP).
+    #[default]
+    LocalAddr,
+    /// Trust an operator-supplied external address verbatim.
+    Override(SocketAddr),
+    /// Ask the LAN gateway for its external IPv4 via UPnP/IGD (the approach
+    /// the vpncloud ecosystem uses) and pair it with the local port.
+    /// Falls back to `LocalAddr` if the gateway can't be reached or doesn't
+    /// support IGD.
+    Upnp,
+}
+
+/// How long a UPnP-discovered external IP is trusted before
+/// `resolve_external_addr` asks the gateway again. A gateway search is a
+/// multicast SSDP round-trip that can take seconds, so this must never run
+/// on every relayed packet.
+const UPNP_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// One cached UPnP lookup result, or a marker that a lookup is already in
+/// flight so concurrent callers wait on it instead of starting their own.
+enum UpnpCacheSlot {
+    Ready { ip: Ipv4Addr, expires_at: Instant },
+    InFlight(Arc<Notify>),
+}
+
+/// Query the LAN gateway for its external IPv4 via UPnP/IGD. Runs on a
+/// blocking task since `igd`'s gateway search/SOAP calls aren't async.
+async fn upnp_external_ip() -> Result<Ipv4Addr> {
+    tokio::task::spawn_blocking(|| {
+        let gateway = igd::search_gateway(Default::default())
+            .map_err(|e| ProxyError::Proxy(format!("UPnP gateway search failed: {}", e)))?;
+        gateway
+            .get_external_ip()
+            .map_err(|e| ProxyError::Proxy(format!("UPnP get_external_ip failed: {}", e)))
+    })
+    .await
+    .map_err(|e| ProxyError::Proxy(format!("UPnP discovery task panicked: {}", e)))?
+}
+
 // ========== RPC Frame helpers ==========
 
 /// Build an RPC frame: [len(4) | seq_no(4) | payload | crc32(4)]
 fn build_rpc_frame(seq_no: i32, payload: &[u8]) -> Vec<u8> {
-    let total_len = (4 + 4 + payload.len() + 4) as u32;
-    let mut f = Vec::with_capacity(total_len as usize);
-    f.extend_from_slice(&total_len.to_le_bytes());
-    f.extend_from_slice(&seq_no.to_le_bytes());
-    f.extend_from_slice(payload);
-    let c = crc32(&f);
-    f.extend_from_slice(&c.to_le_bytes());
-    f
+    RpcFrame::write(seq_no, payload)
 }
 
 /// Read one plaintext RPC frame. Returns (seq_no, payload).
@@ -121,6 +201,8 @@ async fn read_rpc_frame_plaintext(
     rd.read_exact(&mut len_buf).await.map_err(ProxyError::Io)?;
     let total_len = u32::from_le_bytes(len_buf) as usize;
 
+    // Bounds-check before trusting an attacker-controlled length enough to
+    // allocate/read that many more bytes off the wire.
     if total_len < 12 || total_len > (1 << 24) {
         return Err(ProxyError::InvalidHandshake(
             format!("Bad RPC frame length: {}", total_len),
@@ -134,76 +216,23 @@ async fn read_rpc_frame_plaintext(
     full.extend_from_slice(&len_buf);
     full.extend_from_slice(&rest);
 
-    let crc_offset = total_len - 4;
-    let expected_crc = u32::from_le_bytes([
-        full[crc_offset], full[crc_offset + 1],
-        full[crc_offset + 2], full[crc_offset + 3],
-    ]);
-    let actual_crc = crc32(&full[..crc_offset]);
-    if expected_crc != actual_crc {
-        return Err(ProxyError::InvalidHandshake(
-            format!("CRC mismatch: 0x{:08x} vs 0x{:08x}", expected_crc, actual_crc),
-        ));
-    }
-
-    let seq_no = i32::from_le_bytes([full[4], full[5], full[6], full[7]]);
-    let payload = full[8..crc_offset].to_vec();
-    Ok((seq_no, payload))
+    RpcFrame::read(&full)
 }
 
 // ========== RPC Nonce (32 bytes payload) ==========
 
 fn build_nonce_payload(key_selector: u32, crypto_ts: u32, nonce: &[u8; 16]) -> [u8; 32] {
-    let mut p = [0u8; 32];
-    p[0..4].copy_from_slice(&RPC_NONCE_U32.to_le_bytes());
-    p[4..8].copy_from_slice(&key_selector.to_le_bytes());
-    p[8..12].copy_from_slice(&RPC_CRYPTO_AES_U32.to_le_bytes());
-    p[12..16].copy_from_slice(&crypto_ts.to_le_bytes());
-    p[16..32].copy_from_slice(nonce);
-    p
+    NoncePayload::write(key_selector, crypto_ts, nonce)
 }
 
 fn parse_nonce_payload(d: &[u8]) -> Result<(u32, u32, [u8; 16])> {
-    if d.len() < 32 {
-        return Err(ProxyError::InvalidHandshake(
-            format!("Nonce payload too short: {} bytes", d.len()),
-        ));
-    }
-    let t = u32::from_le_bytes([d[0], d[1], d[2], d[3]]);
-    if t != RPC_NONCE_U32 {
-        return Err(ProxyError::InvalidHandshake(
-            format!("Expected RPC_NONCE 0x{:08x}, got 0x{:08x}", RPC_NONCE_U32, t),
-        ));
-    }
-    let schema = u32::from_le_bytes([d[8], d[9], d[10], d[11]]);
-    let ts = u32::from_le_bytes([d[12], d[13], d[14], d[15]]);
-    let mut nonce = [0u8; 16];
-    nonce.copy_from_slice(&d[16..32]);
-    Ok((schema, ts, nonce))
+    NoncePayload::read(d)
 }
 
 // ========== RPC Handshake (32 bytes payload) ==========
 
 fn build_handshake_payload(our_ip: u32, our_port: u16, peer_ip: u32, peer_port: u16) -> [u8; 32] {
-    let mut p = [0u8; 32];
-    p[0..4].copy_from_slice(&RPC_HANDSHAKE_U32.to_le_bytes());
-    // flags = 0 at offset 4..8
-
-    // sender_pid: {ip(4), port(2), pid(2), utime(4)} at offset 8..20
-    p[8..12].copy_from_slice(&our_ip.to_le_bytes());
-    p[12..14].copy_from_slice(&our_port.to_le_bytes());
-    let pid = (std::process::id() & 0xFFFF) as u16;
-    p[14..16].copy_from_slice(&pid.to_le_bytes());
-    let utime = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs() as u32;
-    p[16..20].copy_from_slice(&utime.to_le_bytes());
-
-    // peer_pid: {ip(4), port(2), pid(2), utime(4)} at offset 20..32
-    p[20..24].copy_from_slice(&peer_ip.to_le_bytes());
-    p[24..26].copy_from_slice(&peer_port.to_le_bytes());
-    p
+    HandshakePayload::write(our_ip, our_port, peer_ip, peer_port)
 }
 
 // ========== CBC helpers ==========
@@ -300,14 +329,157 @@ impl ConnRegistry {
 
 // ========== RPC Writer (streaming CBC) ==========
 
+/// Age at which [`RpcWriter::throughput`] restarts its send-rate window.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Per-connection or pool-aggregate throughput, as returned by
+/// [`MePool::throughput_snapshot`] and [`MePool::connection_count`]'s
+/// sibling API.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Throughput {
+    pub bytes_sent: u64,
+    pub bytes_recv: u64,
+    pub frames_sent: u64,
+    pub frames_recv: u64,
+    /// Bytes/sec sent since the start of the current rate window. The
+    /// window is restarted only by a `throughput()` call made once it is at
+    /// least `THROUGHPUT_WINDOW` old, so it spans however long has passed
+    /// since then; it is not a sliding window.
+    pub send_rate_bps: f64,
+}
+
+/// Egress cap for a single `RpcWriter`: steady-state `rate_bytes_per_sec`
+/// with a `burst_bytes` allowance for short spikes above that rate.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitConfig {
+    pub rate_bytes_per_sec: f64,
+    pub burst_bytes: f64,
+}
+
+/// Continuously-refilled byte budget. `acquire` sleeps (rather than
+/// spinning) for however long the bucket needs to cover the deficit.
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(cfg: RateLimitConfig) -> Self {
+        Self {
+            rate: cfg.rate_bytes_per_sec,
+            capacity: cfg.burst_bytes,
+            tokens: cfg.burst_bytes,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let elapsed = self.last_refill.elapsed().as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last_refill = Instant::now();
+    }
+
+    async fn acquire(&mut self, n: f64) {
+        loop {
+            self.refill();
+            if self.tokens >= n {
+                self.tokens -= n;
+                return;
+            }
+            let deficit = n - self.tokens;
+            let wait = Duration::from_secs_f64((deficit / self.rate).max(0.0));
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Ciphertext length `RpcWriter::send` will produce for a given plaintext
+/// payload length: frame header/trailer (`len`+`seq_no`+`crc32` = 12 bytes)
+/// plus CBC padding up to the next 16-byte boundary. Pure function of the
+/// payload length alone, so callers can size a rate-limiter wait for a send
+/// without needing the writer's own lock.
+fn rpc_frame_encoded_len(payload_len: usize) -> usize {
+    let frame_len = payload_len + 12;
+    let pad = (16 - (frame_len % 16)) % 16;
+    frame_len + pad
+}
+
+/// Wait for `payload_len`'s worth of tokens on `writer`'s egress cap, if
+/// one is configured, without holding `writer`'s own lock across the
+/// sleep. `RpcWriter::send` used to run `TokenBucket::acquire` while the
+/// caller's lock on the writer was held, which blocked `reader_loop` from
+/// recording inbound ACK/ANS/PONG frames (and the `last_seen`/RTT
+/// bookkeeping that depends on them) on that same writer for the whole
+/// wait — inflating `rtt_estimate` and risking a keepalive eviction purely
+/// because of our own rate limiter. The token bucket itself now lives
+/// behind its own `Mutex`, separate from the writer's, so the writer lock
+/// is held only long enough to clone the limiter handle and is released
+/// before the wait.
+async fn rate_limit_wait(writer: &Arc<Mutex<RpcWriter>>, payload_len: usize) {
+    let limiter = writer.lock().await.rate_limiter();
+    if let Some(limiter) = limiter {
+        limiter.lock().await.acquire(rpc_frame_encoded_len(payload_len) as f64).await;
+    }
+}
+
 struct RpcWriter {
     writer: tokio::io::WriteHalf<TcpStream>,
     key: [u8; 32],
     iv: [u8; 16],
     seq_no: i32,
+    /// Address this writer is connected to, so the rekey task can reconnect
+    /// a fresh handshake to the same middle-proxy once it ages out.
+    addr: SocketAddr,
+    /// Usage accounting for proactive rekey/rotation: bytes written and
+    /// wall-clock age. Plain atomics (rather than folded into `seq_no`) so
+    /// the rekey task can read them without contending the send-path lock.
+    bytes_sent: AtomicU64,
+    created_at: Instant,
+    /// Cleared once a send fails or a keepalive goes unanswered; such a
+    /// writer is evicted from the round-robin set on the next pass.
+    healthy: AtomicBool,
+    /// Last time we heard anything back from this writer (ACK/ANS/PONG).
+    last_seen: Instant,
+    /// `(ping_id, sent_at)` for a keepalive ping awaiting its PONG.
+    pending_ping: Option<(i64, Instant)>,
+    next_ping_id: i64,
+    /// EWMA of keepalive round-trip time, used by power-of-two-choices
+    /// selection to steer load away from slow/congested links.
+    rtt_estimate: Duration,
+    /// Requests sent but not yet ACKed/ANSed/closed.
+    in_flight: AtomicU64,
+    /// Throughput accounting, surfaced via [`MePool::throughput_snapshot`].
+    bytes_recv: AtomicU64,
+    frames_sent: AtomicU64,
+    frames_recv: AtomicU64,
+    /// Start of the current rate-averaging window and the `bytes_sent`
+    /// total at that point, so `throughput()` can derive a bytes/sec rate
+    /// without a background sampling task.
+    rate_window_start: Instant,
+    rate_window_start_bytes: u64,
+    /// Optional egress cap. Behind its own `Mutex` (rather than embedded
+    /// directly) so [`rate_limit_wait`] can wait on it without holding this
+    /// writer's own lock across the sleep.
+    rate_limiter: Option<Arc<Mutex<TokenBucket>>>,
+    /// Handle to the paired `reader_loop` task, set once it's spawned.
+    /// `remove_writer` aborts it on eviction: `reader_loop` is blocked in
+    /// `rd.read_buf()` on this same socket and a half-dead peer may never
+    /// send the EOF/error that would otherwise end it, leaking both the
+    /// task and the underlying `TcpStream` for the life of the process.
+    reader_task: Option<tokio::task::AbortHandle>,
 }
 
 impl RpcWriter {
+    /// Cheap clone of the rate-limiter handle, for [`rate_limit_wait`].
+    fn rate_limiter(&self) -> Option<Arc<Mutex<TokenBucket>>> {
+        self.rate_limiter.clone()
+    }
+
+    /// Encrypt and write `payload`. Callers that care about the egress cap
+    /// must call [`rate_limit_wait`] themselves *before* taking the lock
+    /// this method runs under — `send` no longer rate-limits internally.
     async fn send(&mut self, payload: &[u8]) -> Result<()> {
         let frame = build_rpc_frame(self.seq_no, payload);
         self.seq_no += 1;
@@ -326,7 +498,67 @@ impl RpcWriter {
         if buf.len() >= 16 {
             self.iv.copy_from_slice(&buf[buf.len() - 16..]);
         }
-        self.writer.write_all(&buf).await.map_err(ProxyError::Io)
+
+        self.bytes_sent.fetch_add(buf.len() as u64, Ordering::Relaxed);
+        self.frames_sent.fetch_add(1, Ordering::Relaxed);
+        let res = self.writer.write_all(&buf).await.map_err(ProxyError::Io);
+        if res.is_err() {
+            self.healthy.store(false, Ordering::Relaxed);
+        }
+        res
+    }
+
+    /// Tally a received, CRC-valid frame into the receive counters.
+    fn record_recv(&self, frame_bytes: u64) {
+        self.bytes_recv.fetch_add(frame_bytes, Ordering::Relaxed);
+        self.frames_recv.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Point-in-time throughput snapshot. Also rolls the rate-averaging
+    /// window forward once it's elapsed, so repeated calls track a moving
+    /// average rather than widening over the connection's whole lifetime.
+    fn throughput(&mut self) -> Throughput {
+        let bytes_sent = self.bytes_sent.load(Ordering::Relaxed);
+        let elapsed = self.rate_window_start.elapsed();
+        let send_rate_bps = if elapsed.as_secs_f64() > 0.0 {
+            bytes_sent.saturating_sub(self.rate_window_start_bytes) as f64 / elapsed.as_secs_f64()
+        } else {
+            0.0
+        };
+        if elapsed >= THROUGHPUT_WINDOW {
+            self.rate_window_start = Instant::now();
+            self.rate_window_start_bytes = bytes_sent;
+        }
+        Throughput {
+            bytes_sent,
+            bytes_recv: self.bytes_recv.load(Ordering::Relaxed),
+            frames_sent: self.frames_sent.load(Ordering::Relaxed),
+            frames_recv: self.frames_recv.load(Ordering::Relaxed),
+            send_rate_bps,
+        }
+    }
+
+    /// Whether this writer has crossed a usage or age threshold and should
+    /// be rotated out for a fresh handshake.
+    fn exceeds(&self, max_bytes: u64, max_age: Duration) -> bool {
+        self.bytes_sent.load(Ordering::Relaxed) >= max_bytes || self.created_at.elapsed() >= max_age
+    }
+
+    /// Send an `RPC_PING` and record it as pending, awaiting its `RPC_PONG`.
+    async fn send_ping(&mut self) -> Result<()> {
+        let ping_id = self.next_ping_id;
+        self.next_ping_id += 1;
+        let mut p = Vec::with_capacity(12);
+        p.extend_from_slice(&RPC_PING_U32.to_le_bytes());
+        p.extend_from_slice(&ping_id.to_le_bytes());
+        self.send(&p).await?;
+        self.pending_ping = Some((ping_id, Instant::now()));
+        Ok(())
+    }
+
+    /// Whether a previously-sent keepalive ping went unanswered past `timeout`.
+    fn keepalive_timed_out(&self, timeout: Duration) -> bool {
+        matches!(self.pending_ping, Some((_, sent)) if sent.elapsed() >= timeout)
     }
 }
 
@@ -350,100 +582,418 @@ fn build_proxy_req_payload(
     // Our proto_flags_for_tag returns: 0x8 | 0x1000 | 0x20000 | transport_flags
     // So we are good.
 
-    let b_cap = 128 + data.len();
-    let mut b = Vec::with_capacity(b_cap);
+    let mut w = Writer::with_capacity(128 + data.len());
 
-    b.extend_from_slice(&RPC_PROXY_REQ_U32.to_le_bytes());
-    b.extend_from_slice(&flags.to_le_bytes());
-    b.extend_from_slice(&conn_id.to_le_bytes());
+    w.u32_le(RPC_PROXY_REQ_U32)
+        .u32_le(flags)
+        .u64_le(conn_id);
 
     // Client IP (16 bytes IPv4-mapped-v6) + port (4 bytes)
     match client_addr.ip() {
-        IpAddr::V4(v4) => b.extend_from_slice(&ipv4_to_mapped_v6(v4)),
-        IpAddr::V6(v6) => b.extend_from_slice(&v6.octets()),
-    }
-    b.extend_from_slice(&(client_addr.port() as u32).to_le_bytes());
+        IpAddr::V4(v4) => w.bytes(&ipv4_to_mapped_v6(v4)),
+        IpAddr::V6(v6) => w.bytes(&v6.octets()),
+    };
+    w.u32_le(client_addr.port() as u32);
 
     // Our IP (16 bytes) + port (4 bytes)
     match our_addr.ip() {
-        IpAddr::V4(v4) => b.extend_from_slice(&ipv4_to_mapped_v6(v4)),
-        IpAddr::V6(v6) => b.extend_from_slice(&v6.octets()),
-    }
-    b.extend_from_slice(&(our_addr.port() as u32).to_le_bytes());
+        IpAddr::V4(v4) => w.bytes(&ipv4_to_mapped_v6(v4)),
+        IpAddr::V6(v6) => w.bytes(&v6.octets()),
+    };
+    w.u32_le(our_addr.port() as u32);
 
     // Extra section (proxy_tag)
     if flags & 12 != 0 {
-        let extra_start = b.len();
-        b.extend_from_slice(&0u32.to_le_bytes()); // placeholder
-
-        if let Some(tag) = proxy_tag {
-            b.extend_from_slice(&TL_PROXY_TAG_U32.to_le_bytes());
-            // TL string encoding
-            if tag.len() < 254 {
-                b.push(tag.len() as u8);
-                b.extend_from_slice(tag);
-                let pad = (4 - ((1 + tag.len()) % 4)) % 4;
-                b.extend(std::iter::repeat(0u8).take(pad));
-            } else {
-                b.push(0xfe);
-                let len_bytes = (tag.len() as u32).to_le_bytes();
-                b.extend_from_slice(&len_bytes[..3]);
-                b.extend_from_slice(tag);
-                let pad = (4 - (tag.len() % 4)) % 4;
-                b.extend(std::iter::repeat(0u8).take(pad));
-            }
+        ProxyReqExtra::write(&mut w, proxy_tag);
+    }
+
+    w.bytes(data);
+    w.into_vec()
+}
+
+// ========== Diagnostic Frame Tap ==========
+
+/// Which way a captured frame traveled, relative to this proxy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDirection {
+    Inbound,
+    Outbound,
+}
+
+/// One captured RPC frame, decoded just enough for diagnostics — not the
+/// full payload, so capturing stays allocation-light even under load.
+#[derive(Debug, Clone, Copy)]
+pub struct CapturedFrame {
+    pub pt: u32,
+    pub conn_id: u64,
+    pub len: usize,
+    pub direction: FrameDirection,
+    pub at: Instant,
+}
+
+/// Opt-in ring buffer of the most recent [`CapturedFrame`]s flowing through
+/// a `MePool`, for inspecting live traffic without paying for `trace!` on
+/// the hot path. A plain `std::sync::Mutex` rather than `tokio::sync::Mutex`
+/// guards the ring: a capture is a short, non-awaiting push/pop, so there's
+/// no point paying for an async-aware lock here.
+pub struct FrameTap {
+    capacity: usize,
+    ring: std::sync::Mutex<VecDeque<CapturedFrame>>,
+    total_frames: AtomicU64,
+    overflow_occurred: AtomicBool,
+}
+
+impl FrameTap {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ring: std::sync::Mutex::new(VecDeque::with_capacity(capacity)),
+            total_frames: AtomicU64::new(0),
+            overflow_occurred: AtomicBool::new(false),
         }
+    }
 
-        let extra_bytes = (b.len() - extra_start - 4) as u32;
-        let eb = extra_bytes.to_le_bytes();
-        b[extra_start..extra_start + 4].copy_from_slice(&eb);
+    fn capture(&self, pt: u32, conn_id: u64, len: usize, direction: FrameDirection) {
+        let mut ring = self.ring.lock().unwrap();
+        if ring.len() >= self.capacity {
+            ring.pop_front();
+            self.overflow_occurred.store(true, Ordering::Relaxed);
+        }
+        ring.push_back(CapturedFrame { pt, conn_id, len, direction, at: Instant::now() });
+        drop(ring);
+        self.total_frames.fetch_add(1, Ordering::Relaxed);
     }
 
-    b.extend_from_slice(data);
-    b
+    /// Currently buffered frames, newest first.
+    pub fn snapshot(&self) -> Vec<CapturedFrame> {
+        self.ring.lock().unwrap().iter().rev().copied().collect()
+    }
+
+    /// Total frames ever captured, including ones since overwritten.
+    pub fn total_frames(&self) -> u64 {
+        self.total_frames.load(Ordering::Relaxed)
+    }
+
+    /// Whether the ring has ever wrapped and dropped an old entry.
+    pub fn overflow_occurred(&self) -> bool {
+        self.overflow_occurred.load(Ordering::Relaxed)
+    }
 }
 
 // ========== ME Pool ==========
 
+/// Default byte threshold before a writer is proactively rekeyed: 1 GiB.
+pub const DEFAULT_REKEY_MAX_BYTES: u64 = 1 << 30;
+/// Default age threshold before a writer is proactively rekeyed: 6 hours.
+pub const DEFAULT_REKEY_MAX_AGE: Duration = Duration::from_secs(6 * 3600);
+
+/// How `send_proxy_req` picks a writer out of the live set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionMode {
+    /// Scan every live writer and send on whichever minimizes
+    /// `in_flight + k * rtt_ewma`, breaking ties with round-robin. The
+    /// default: strictly better than sampling two when the full live set
+    /// is small enough to scan on every send (it always is here — pool
+    /// sizes are a handful of DC connections, not thousands).
+    LeastLoaded,
+    /// Sample two live writers and send on whichever has the lower
+    /// `in_flight * rtt_estimate` score. Steers load away from DCs that are
+    /// slow or backed up without needing a global view of every writer.
+    PowerOfTwoChoices,
+    /// Blind round-robin over the live set; kept as a fallback for when RTT
+    /// samples aren't trustworthy yet (e.g. right after a bulk reconnect).
+    RoundRobin,
+}
+
+/// Weight `k` applied to `rtt_ewma` (in milliseconds) against `in_flight`
+/// in [`SelectionMode::LeastLoaded`]'s score. One extra in-flight request
+/// is treated as roughly as bad as 20ms of extra RTT.
+const LEAST_LOADED_RTT_WEIGHT_MS: f64 = 1.0 / 20.0;
+
+/// A Telegram proxy-secret plus its precomputed `key_selector`, so the
+/// handshake doesn't need to re-derive the selector from the raw bytes on
+/// every retry.
+struct SecretEntry {
+    /// Telegram proxy-secret (binary, 32-512 bytes)
+    secret: Vec<u8>,
+    /// First 4 bytes of `secret` as LE u32.
+    /// C: main_secret.key_signature via union { char secret[]; int key_signature; }
+    key_selector: u32,
+}
+
+impl SecretEntry {
+    fn new(secret: Vec<u8>) -> Self {
+        let key_selector = if secret.len() >= 4 {
+            u32::from_le_bytes([secret[0], secret[1], secret[2], secret[3]])
+        } else {
+            0
+        };
+        Self { secret, key_selector }
+    }
+}
+
+/// Cap on how many proxy-secrets `MePool` tracks at once. Telegram only
+/// ever has a couple of key_signatures valid during a rotation window, so
+/// this just bounds the retry fan-out, not a real limit in practice.
+const MAX_TRACKED_SECRETS: usize = 4;
+
 pub struct MePool {
     registry: Arc<ConnRegistry>,
     writers: Arc<RwLock<Vec<Arc<Mutex<RpcWriter>>>>>,
     rr: AtomicU64,
     proxy_tag: Option<Vec<u8>>,
-    /// Telegram proxy-secret (binary, 32-512 bytes)
-    proxy_secret: Vec<u8>,
+    /// Ordered list of proxy-secrets to try during handshake, most-recently
+    /// refreshed first. More than one entry only matters while Telegram is
+    /// mid-rotation between key_signatures.
+    secrets: RwLock<Vec<SecretEntry>>,
     pool_size: usize,
+    /// How to determine the address we report to the ME server, for
+    /// operators behind NAT. See [`ExternalAddrMode`].
+    external_addr_mode: ExternalAddrMode,
+    /// Usage/age thresholds past which a writer is rotated to a fresh
+    /// handshake rather than reused for its whole process lifetime.
+    rekey_max_bytes: u64,
+    rekey_max_age: Duration,
+    selection_mode: SelectionMode,
+    /// Nudges `me_health_monitor` as soon as a writer leaves the pool, so a
+    /// broken connection gets a reconnect attempt immediately instead of
+    /// waiting for the next poll tick.
+    writer_removed: Notify,
+    /// Egress cap applied to every writer this pool creates, if configured.
+    rate_limit: Option<RateLimitConfig>,
+    /// Recent-traffic ring buffer, present only when diagnostics are
+    /// enabled (see [`FrameTap`]).
+    frame_tap: Option<Arc<FrameTap>>,
+    /// Cached result of the last UPnP external-IP lookup, only ever
+    /// populated when `external_addr_mode` is [`ExternalAddrMode::Upnp`].
+    /// See [`MePool::cached_upnp_ip`].
+    upnp_cache: Mutex<Option<UpnpCacheSlot>>,
 }
 
 impl MePool {
     pub fn new(proxy_tag: Option<Vec<u8>>, proxy_secret: Vec<u8>) -> Arc<Self> {
+        Self::new_with_rekey_thresholds(
+            proxy_tag,
+            proxy_secret,
+            DEFAULT_REKEY_MAX_BYTES,
+            DEFAULT_REKEY_MAX_AGE,
+        )
+    }
+
+    pub fn new_with_rekey_thresholds(
+        proxy_tag: Option<Vec<u8>>,
+        proxy_secret: Vec<u8>,
+        rekey_max_bytes: u64,
+        rekey_max_age: Duration,
+    ) -> Arc<Self> {
+        Self::new_with_selection(
+            proxy_tag,
+            proxy_secret,
+            rekey_max_bytes,
+            rekey_max_age,
+            SelectionMode::LeastLoaded,
+        )
+    }
+
+    pub fn new_with_selection(
+        proxy_tag: Option<Vec<u8>>,
+        proxy_secret: Vec<u8>,
+        rekey_max_bytes: u64,
+        rekey_max_age: Duration,
+        selection_mode: SelectionMode,
+    ) -> Arc<Self> {
+        Self::new_with_external_addr_mode(
+            proxy_tag,
+            proxy_secret,
+            rekey_max_bytes,
+            rekey_max_age,
+            selection_mode,
+            ExternalAddrMode::LocalAddr,
+        )
+    }
+
+    pub fn new_with_external_addr_mode(
+        proxy_tag: Option<Vec<u8>>,
+        proxy_secret: Vec<u8>,
+        rekey_max_bytes: u64,
+        rekey_max_age: Duration,
+        selection_mode: SelectionMode,
+        external_addr_mode: ExternalAddrMode,
+    ) -> Arc<Self> {
+        Self::new_with_rate_limit(
+            proxy_tag,
+            proxy_secret,
+            rekey_max_bytes,
+            rekey_max_age,
+            selection_mode,
+            external_addr_mode,
+            None,
+        )
+    }
+
+    pub fn new_with_rate_limit(
+        proxy_tag: Option<Vec<u8>>,
+        proxy_secret: Vec<u8>,
+        rekey_max_bytes: u64,
+        rekey_max_age: Duration,
+        selection_mode: SelectionMode,
+        external_addr_mode: ExternalAddrMode,
+        rate_limit: Option<RateLimitConfig>,
+    ) -> Arc<Self> {
+        Self::new_with_frame_tap_capacity(
+            proxy_tag,
+            proxy_secret,
+            rekey_max_bytes,
+            rekey_max_age,
+            selection_mode,
+            external_addr_mode,
+            rate_limit,
+            None,
+        )
+    }
+
+    /// Base constructor: every other `new_with_*` delegates down to this one
+    /// with a default for whichever knob it doesn't expose. Diagnostics are
+    /// off (`None`) unless an operator opts in with a ring capacity.
+    pub fn new_with_frame_tap_capacity(
+        proxy_tag: Option<Vec<u8>>,
+        proxy_secret: Vec<u8>,
+        rekey_max_bytes: u64,
+        rekey_max_age: Duration,
+        selection_mode: SelectionMode,
+        external_addr_mode: ExternalAddrMode,
+        rate_limit: Option<RateLimitConfig>,
+        frame_tap_capacity: Option<usize>,
+    ) -> Arc<Self> {
         Arc::new(Self {
             registry: Arc::new(ConnRegistry::new()),
             writers: Arc::new(RwLock::new(Vec::new())),
             rr: AtomicU64::new(0),
             proxy_tag,
-            proxy_secret,
+            secrets: RwLock::new(vec![SecretEntry::new(proxy_secret)]),
             pool_size: 2,
+            rekey_max_bytes,
+            rekey_max_age,
+            selection_mode,
+            external_addr_mode,
+            writer_removed: Notify::new(),
+            rate_limit,
+            frame_tap: frame_tap_capacity.map(|cap| Arc::new(FrameTap::new(cap))),
+            upnp_cache: Mutex::new(None),
         })
     }
 
+    /// Drop `w` from the live writer set and wake the health monitor so it
+    /// can reconnect the freed slot right away instead of on its next poll.
+    async fn remove_writer(&self, w: &Arc<Mutex<RpcWriter>>) {
+        let mut ws = self.writers.write().await;
+        ws.retain(|o| !Arc::ptr_eq(o, w));
+        drop(ws);
+
+        // Close the socket so a `reader_loop` blocked in `rd.read_buf()` on
+        // a half-dead link doesn't leak its task and `TcpStream` forever
+        // waiting for an EOF the peer may never send. `shutdown` alone only
+        // half-closes our write direction (a FIN the peer could ignore), so
+        // also abort the reader task directly — it owns the other half and
+        // dropping it there releases the fd regardless of what the peer does.
+        {
+            let mut guard = w.lock().await;
+            if let Some(task) = guard.reader_task.take() {
+                task.abort();
+            }
+            if let Err(e) = guard.writer.shutdown().await {
+                debug!(error = %e, "ME writer shutdown failed (already closed?)");
+            }
+        }
+
+        self.writer_removed.notify_one();
+    }
+
+    /// Resolve the address to embed in the RPC handshake / `RPC_PROXY_REQ`
+    /// "our IP" fields, given the socket's own local address, per
+    /// `external_addr_mode`.
+    async fn resolve_external_addr(&self, local_addr: SocketAddr) -> SocketAddr {
+        match &self.external_addr_mode {
+            ExternalAddrMode::LocalAddr => local_addr,
+            ExternalAddrMode::Override(addr) => *addr,
+            ExternalAddrMode::Upnp => match self.cached_upnp_ip().await {
+                Some(ip) => SocketAddr::new(IpAddr::V4(ip), local_addr.port()),
+                None => local_addr,
+            },
+        }
+    }
+
+    /// Resolve the gateway's external IP via UPnP, reusing a cached result
+    /// for up to [`UPNP_CACHE_TTL`] and deduping concurrent misses into a
+    /// single gateway search. Called from every `resolve_external_addr` in
+    /// `ExternalAddrMode::Upnp`, i.e. on every send — without this cache
+    /// that would mean a multicast SSDP discovery per relayed chunk.
+    async fn cached_upnp_ip(&self) -> Option<Ipv4Addr> {
+        loop {
+            let mut slot = self.upnp_cache.lock().await;
+            match &*slot {
+                Some(UpnpCacheSlot::Ready { ip, expires_at }) if *expires_at > Instant::now() => {
+                    return Some(*ip);
+                }
+                Some(UpnpCacheSlot::InFlight(notify)) => {
+                    let notify = notify.clone();
+                    drop(slot);
+                    notify.notified().await;
+                    continue;
+                }
+                _ => {
+                    let notify = Arc::new(Notify::new());
+                    *slot = Some(UpnpCacheSlot::InFlight(notify.clone()));
+                    drop(slot);
+
+                    let result = upnp_external_ip().await;
+
+                    let mut slot = self.upnp_cache.lock().await;
+                    return match result {
+                        Ok(ip) => {
+                            *slot = Some(UpnpCacheSlot::Ready {
+                                ip,
+                                expires_at: Instant::now() + UPNP_CACHE_TTL,
+                            });
+                            notify.notify_waiters();
+                            Some(ip)
+                        }
+                        Err(e) => {
+                            warn!(error = %e, "UPnP external address discovery failed, using local addr");
+                            *slot = None;
+                            notify.notify_waiters();
+                            None
+                        }
+                    };
+                }
+            }
+        }
+    }
+
     pub fn registry(&self) -> &Arc<ConnRegistry> {
         &self.registry
     }
 
-    fn writers_arc(&self) -> Arc<RwLock<Vec<Arc<Mutex<RpcWriter>>>>> {
-        self.writers.clone()
+    /// Replace the secret list wholesale (used by tests/callers that want a
+    /// specific multi-secret starting set instead of the single-secret
+    /// constructors).
+    pub async fn set_secrets(&self, secrets: Vec<Vec<u8>>) {
+        let mut s = self.secrets.write().await;
+        *s = secrets.into_iter().map(SecretEntry::new).collect();
     }
 
-    /// key_selector = first 4 bytes of proxy-secret as LE u32
-    /// C: main_secret.key_signature via union { char secret[]; int key_signature; }
-    fn key_selector(&self) -> u32 {
-        if self.proxy_secret.len() >= 4 {
-            u32::from_le_bytes([
-                self.proxy_secret[0], self.proxy_secret[1],
-                self.proxy_secret[2], self.proxy_secret[3],
-            ])
-        } else { 0 }
+    /// Hot-swap a freshly downloaded proxy-secret into the front of the
+    /// secret list so new handshakes pick it up first, while keeping the
+    /// prior secret(s) around for any ME server that hasn't rotated to the
+    /// new key_signature yet. Existing connections are unaffected: their
+    /// CBC keys were already derived at connect time and don't depend on
+    /// this list.
+    async fn push_secret(&self, secret: Vec<u8>) {
+        let entry = SecretEntry::new(secret);
+        let mut secrets = self.secrets.write().await;
+        secrets.retain(|e| e.secret != entry.secret);
+        secrets.insert(0, entry);
+        secrets.truncate(MAX_TRACKED_SECRETS);
     }
 
     pub async fn init(
@@ -452,14 +1002,15 @@ impl MePool {
         rng: &SecureRandom,
     ) -> Result<()> {
         let addrs = &*TG_MIDDLE_PROXIES_FLAT_V4;
-        let ks = self.key_selector();
+        let secrets = self.secrets.read().await;
         info!(
             me_servers = addrs.len(),
             pool_size,
-            key_selector = format_args!("0x{:08x}", ks),
-            secret_len = self.proxy_secret.len(),
+            secrets = secrets.len(),
+            key_selector = format_args!("0x{:08x}", secrets.first().map(|e| e.key_selector).unwrap_or(0)),
             "Initializing ME pool"
         );
+        drop(secrets);
 
         for &(ip, port) in addrs.iter() {
             for i in 0..pool_size {
@@ -480,12 +1031,47 @@ impl MePool {
         Ok(())
     }
 
-    async fn connect_one(
+    /// Try every known proxy-secret in order until one handshakes
+    /// successfully, so a Telegram-side key_signature rotation doesn't take
+    /// down the whole pool until restart.
+    async fn connect_one(self: &Arc<Self>, addr: SocketAddr, rng: &SecureRandom) -> Result<()> {
+        let secrets: Vec<(Vec<u8>, u32)> = self
+            .secrets
+            .read()
+            .await
+            .iter()
+            .map(|e| (e.secret.clone(), e.key_selector))
+            .collect();
+        if secrets.is_empty() {
+            return Err(ProxyError::Proxy("no proxy secrets configured".into()));
+        }
+
+        for (secret, ks) in &secrets {
+            match self.try_handshake_with_secret(addr, rng, secret, *ks).await {
+                Ok(true) => return Ok(()),
+                Ok(false) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(ProxyError::InvalidHandshake(
+            "ME rejected handshake for all known proxy-secrets".into(),
+        ))
+    }
+
+    /// One handshake attempt against `addr` using a specific `secret` /
+    /// `ks` (key_selector). Returns `Ok(true)` on success (writer installed
+    /// and reader spawned), `Ok(false)` if the ME server rejected this
+    /// particular key_selector (caller should retry with the next secret),
+    /// and `Err` for anything else (network/protocol failure, which aborts
+    /// the whole connect attempt regardless of secret).
+    async fn try_handshake_with_secret(
         self: &Arc<Self>,
         addr: SocketAddr,
         rng: &SecureRandom,
-    ) -> Result<()> {
-        let secret = &self.proxy_secret;
+        secret: &[u8],
+        ks: u32,
+    ) -> Result<bool> {
         if secret.len() < 32 {
             return Err(ProxyError::Proxy("proxy-secret too short for ME auth".into()));
         }
@@ -510,7 +1096,6 @@ impl MePool {
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs() as u32;
-        let ks = self.key_selector();
 
         let nonce_payload = build_nonce_payload(ks, crypto_ts, &my_nonce);
         let nonce_frame = build_rpc_frame(-2, &nonce_payload);
@@ -594,8 +1179,11 @@ impl MePool {
         );
 
         // ===== 4. Send encrypted handshake (seq=-1) =====
+        // `sender_pid` should carry our real external address, not the
+        // (possibly RFC1918) local one the socket sees behind NAT.
+        let reported_addr = self.resolve_external_addr(local_addr).await;
         let hs_payload = build_handshake_payload(
-            client_ip, local_addr.port(),
+            addr_to_ip_u32(&reported_addr), reported_addr.port(),
             server_ip, peer_addr.port(),
         );
         let hs_frame = build_rpc_frame(-1, &hs_payload);
@@ -681,9 +1269,8 @@ impl MePool {
                     let err_code = if frame.len() >= 16 {
                         i32::from_le_bytes([frame[12], frame[13], frame[14], frame[15]])
                     } else { -1 };
-                    return Err(ProxyError::InvalidHandshake(
-                        format!("ME rejected handshake (error={})", err_code),
-                    ));
+                    debug!(%addr, key_sel = format_args!("0x{:08x}", ks), error = err_code, "ME rejected key_selector, trying next secret");
+                    return Ok(false);
                 }
                 if hs_type != RPC_HANDSHAKE_U32 {
                     return Err(ProxyError::InvalidHandshake(
@@ -708,23 +1295,100 @@ impl MePool {
             key: wk,
             iv: write_iv,
             seq_no: 0,
+            addr,
+            bytes_sent: AtomicU64::new(0),
+            created_at: Instant::now(),
+            healthy: AtomicBool::new(true),
+            last_seen: Instant::now(),
+            pending_ping: None,
+            next_ping_id: 0,
+            rtt_estimate: Duration::ZERO,
+            in_flight: AtomicU64::new(0),
+            bytes_recv: AtomicU64::new(0),
+            frames_sent: AtomicU64::new(0),
+            frames_recv: AtomicU64::new(0),
+            rate_window_start: Instant::now(),
+            rate_window_start_bytes: 0,
+            rate_limiter: self.rate_limit.map(|cfg| Arc::new(Mutex::new(TokenBucket::new(cfg)))),
+            reader_task: None,
         }));
         self.writers.write().await.push(rpc_w.clone());
 
         let reg = self.registry.clone();
         let w_pong = rpc_w.clone();
-        let w_pool = self.writers_arc();
-        tokio::spawn(async move {
-            if let Err(e) = reader_loop(rd, rk, read_iv, reg, enc_buf, dec_buf, w_pong.clone()).await {
+        let pool = self.clone();
+        let frame_tap = self.frame_tap.clone();
+        // Merge the handshake's leftover decrypted-but-unparsed bytes and
+        // undecrypted ciphertext into the single buffer reader_loop expects,
+        // decrypted prefix first so its `decrypted_len` split lines up.
+        let decrypted_len = dec_buf.len();
+        let mut recv_buf = dec_buf;
+        recv_buf.unsplit(enc_buf);
+        let reader_handle = tokio::spawn(async move {
+            if let Err(e) = reader_loop(rd, rk, read_iv, reg, recv_buf, decrypted_len, w_pong.clone(), frame_tap).await {
                 warn!(error = %e, "ME reader ended");
             }
-            // Remove dead writer from pool
-            let mut ws = w_pool.write().await;
-            ws.retain(|w| !Arc::ptr_eq(w, &w_pong));
-            info!(remaining = ws.len(), "Dead ME writer removed from pool");
+            pool.remove_writer(&w_pong).await;
+            info!("Dead ME writer removed from pool");
         });
+        rpc_w.lock().await.reader_task = Some(reader_handle.abort_handle());
 
-        Ok(())
+        Ok(true)
+    }
+
+    /// Sample two distinct writers from `live` and return whichever has the
+    /// lower `in_flight * rtt_estimate` score. Falls back to the single
+    /// candidate when only one writer is live.
+    async fn pick_power_of_two(&self, live: &[Arc<Mutex<RpcWriter>>]) -> Arc<Mutex<RpcWriter>> {
+        if live.len() == 1 {
+            return live[0].clone();
+        }
+        // Dependency-free pick of two distinct indices: wall-clock
+        // nanoseconds give enough entropy for load-spreading purposes
+        // without pulling in a full RNG for this.
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos() as usize;
+        let i = nanos % live.len();
+        let mut j = (nanos / live.len()) % live.len();
+        if j == i {
+            j = (j + 1) % live.len();
+        }
+        let (a, b) = (&live[i], &live[j]);
+        let score = |g: &RpcWriter| {
+            g.in_flight.load(Ordering::Relaxed) as u128 * g.rtt_estimate.as_nanos().max(1)
+        };
+        let (score_a, score_b) = (score(&*a.lock().await), score(&*b.lock().await));
+        if score_a <= score_b { a.clone() } else { b.clone() }
+    }
+
+    /// Scan every writer in `live` and return whichever minimizes
+    /// `in_flight + k * rtt_ewma_ms`. Ties (most commonly many freshly
+    /// connected writers all sitting at `in_flight=0, rtt_ewma=0`) are
+    /// broken with the same round-robin counter used by
+    /// [`SelectionMode::RoundRobin`], so load still spreads evenly among
+    /// equally-good candidates instead of pinning to `live[0]`.
+    async fn pick_least_loaded(&self, live: &[Arc<Mutex<RpcWriter>>]) -> Arc<Mutex<RpcWriter>> {
+        if live.len() == 1 {
+            return live[0].clone();
+        }
+        let mut best_score = f64::INFINITY;
+        let mut best: Vec<usize> = Vec::new();
+        for (idx, w) in live.iter().enumerate() {
+            let g = w.lock().await;
+            let score = g.in_flight.load(Ordering::Relaxed) as f64
+                + LEAST_LOADED_RTT_WEIGHT_MS * g.rtt_estimate.as_secs_f64() * 1000.0;
+            if score < best_score {
+                best_score = score;
+                best.clear();
+                best.push(idx);
+            } else if score == best_score {
+                best.push(idx);
+            }
+        }
+        let tie_idx = self.rr.fetch_add(1, Ordering::Relaxed) as usize % best.len();
+        live[best[tie_idx]].clone()
     }
 
     pub async fn send_proxy_req(
@@ -735,25 +1399,55 @@ impl MePool {
         data: &[u8],
         proto_flags: u32,
     ) -> Result<()> {
+        // Report our real external address, not whatever (possibly
+        // RFC1918) local address the caller saw the listener bound to.
+        let our_addr = self.resolve_external_addr(our_addr).await;
         let payload = build_proxy_req_payload(
             conn_id, client_addr, our_addr, data,
             self.proxy_tag.as_deref(), proto_flags,
         );
         loop {
             let ws = self.writers.read().await;
-            if ws.is_empty() {
+            let live: Vec<_> = ws
+                .iter()
+                .filter(|w| {
+                    // A writer can be present-but-unhealthy between a
+                    // failed send/keepalive and the supervisor evicting it.
+                    match w.try_lock() {
+                        Ok(g) => g.healthy.load(Ordering::Relaxed),
+                        Err(_) => true,
+                    }
+                })
+                .cloned()
+                .collect();
+            if live.is_empty() {
                 return Err(ProxyError::Proxy("All ME connections dead".into()));
             }
-            let idx = self.rr.fetch_add(1, Ordering::Relaxed) as usize % ws.len();
-            let w = ws[idx].clone();
+            let w = match self.selection_mode {
+                SelectionMode::RoundRobin => {
+                    let idx = self.rr.fetch_add(1, Ordering::Relaxed) as usize % live.len();
+                    live[idx].clone()
+                }
+                SelectionMode::PowerOfTwoChoices => self.pick_power_of_two(&live).await,
+                SelectionMode::LeastLoaded => self.pick_least_loaded(&live).await,
+            };
             drop(ws);
+            w.lock().await.in_flight.fetch_add(1, Ordering::Relaxed);
+            rate_limit_wait(&w, payload.len()).await;
             match w.lock().await.send(&payload).await {
-                Ok(()) => return Ok(()),
+                Ok(()) => {
+                    self.tap_outbound(RPC_PROXY_REQ_U32, conn_id, payload.len());
+                    return Ok(());
+                }
                 Err(e) => {
+                    w.lock()
+                        .await
+                        .in_flight
+                        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| Some(v.saturating_sub(1)))
+                        .ok();
                     warn!(error = %e, "ME write failed, removing dead conn");
-                    let mut ws = self.writers.write().await;
-                    ws.retain(|o| !Arc::ptr_eq(o, &w));
-                    if ws.is_empty() {
+                    self.remove_writer(&w).await;
+                    if self.writers.read().await.is_empty() {
                         return Err(ProxyError::Proxy("All ME connections dead".into()));
                     }
                 }
@@ -769,110 +1463,200 @@ impl MePool {
             let mut p = Vec::with_capacity(12);
             p.extend_from_slice(&RPC_CLOSE_EXT_U32.to_le_bytes());
             p.extend_from_slice(&conn_id.to_le_bytes());
+            rate_limit_wait(&w, p.len()).await;
             if let Err(e) = w.lock().await.send(&p).await {
                 debug!(error = %e, "ME close write failed");
-                let mut ws = self.writers.write().await;
-                ws.retain(|o| !Arc::ptr_eq(o, &w));
+                self.remove_writer(&w).await;
+            } else {
+                self.tap_outbound(RPC_CLOSE_EXT_U32, conn_id, p.len());
             }
         }
         self.registry.unregister(conn_id).await;
         Ok(())
     }
 
+    fn tap_outbound(&self, pt: u32, conn_id: u64, len: usize) {
+        if let Some(tap) = &self.frame_tap {
+            tap.capture(pt, conn_id, len, FrameDirection::Outbound);
+        }
+    }
+
+    /// Recent-traffic snapshot, newest first, if diagnostics are enabled.
+    pub fn frame_tap_snapshot(&self) -> Option<Vec<CapturedFrame>> {
+        self.frame_tap.as_ref().map(|t| t.snapshot())
+    }
+
     pub fn connection_count(&self) -> usize {
         self.writers.try_read().map(|w| w.len()).unwrap_or(0)
     }
+
+    /// Per-connection throughput (keyed by the address each writer is
+    /// connected to) plus the pool-wide total, for bandwidth visibility.
+    pub async fn throughput_snapshot(&self) -> (Vec<(SocketAddr, Throughput)>, Throughput) {
+        let ws = self.writers.read().await.clone();
+        let mut per_conn = Vec::with_capacity(ws.len());
+        let mut total = Throughput::default();
+        for w in &ws {
+            let mut g = w.lock().await;
+            let addr = g.addr;
+            let t = g.throughput();
+            total.bytes_sent += t.bytes_sent;
+            total.bytes_recv += t.bytes_recv;
+            total.frames_sent += t.frames_sent;
+            total.frames_recv += t.frames_recv;
+            total.send_rate_bps += t.send_rate_bps;
+            per_conn.push((addr, t));
+        }
+        (per_conn, total)
+    }
 }
 
 // ========== Reader Loop ==========
 
+/// Decrement a writer's in-flight counter, saturating at zero so a stray
+/// extra ACK/Close for the same request can't wrap it negative.
+async fn dec_in_flight(writer: &Arc<Mutex<RpcWriter>>) {
+    writer
+        .lock()
+        .await
+        .in_flight
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| Some(v.saturating_sub(1)))
+        .ok();
+}
+
+/// Reads, decrypts, and frame-parses directly out of one growable
+/// `BytesMut`: `read_buf` fills its spare tail with no intermediate
+/// allocation, `decrypted_len` tracks how much of the front is plaintext
+/// already, and each complete 16-byte-aligned ciphertext prefix beyond that
+/// point is decrypted in place. The CBC IV for the next batch is captured
+/// from the last ciphertext block *before* it's overwritten by the decrypt.
 async fn reader_loop(
     mut rd: tokio::io::ReadHalf<TcpStream>,
     dk: [u8; 32],
     mut div: [u8; 16],
     reg: Arc<ConnRegistry>,
-    mut enc_leftover: BytesMut,
-    mut dec: BytesMut,
+    mut buf: BytesMut,
+    mut decrypted_len: usize,
     writer: Arc<Mutex<RpcWriter>>,
+    frame_tap: Option<Arc<FrameTap>>,
 ) -> Result<()> {
-    let mut raw = enc_leftover;
     loop {
-        let mut tmp = [0u8; 16384];
-        let n = rd.read(&mut tmp).await.map_err(ProxyError::Io)?;
+        buf.reserve(16384);
+        let n = rd.read_buf(&mut buf).await.map_err(ProxyError::Io)?;
         if n == 0 { return Ok(()); }
-        raw.extend_from_slice(&tmp[..n]);
 
-        // Decrypt complete 16-byte blocks
-        let blocks = raw.len() / 16 * 16;
-        if blocks > 0 {
+        // Decrypt whatever whole 16-byte blocks have arrived since the last
+        // pass, in place; a sub-block remainder stays buffered as ciphertext.
+        let ciphertext_len = buf.len() - decrypted_len;
+        let whole_blocks = ciphertext_len / 16 * 16;
+        if whole_blocks > 0 {
+            let enc_start = decrypted_len;
+            let enc_end = enc_start + whole_blocks;
             let mut new_iv = [0u8; 16];
-            new_iv.copy_from_slice(&raw[blocks - 16..blocks]);
-            let mut chunk = vec![0u8; blocks];
-            chunk.copy_from_slice(&raw[..blocks]);
+            new_iv.copy_from_slice(&buf[enc_end - 16..enc_end]);
             AesCbc::new(dk, div)
-                .decrypt_in_place(&mut chunk)
+                .decrypt_in_place(&mut buf[enc_start..enc_end])
                 .map_err(|e| ProxyError::Crypto(format!("{}", e)))?;
             div = new_iv;
-            dec.extend_from_slice(&chunk);
-            let _ = raw.split_to(blocks);
+            decrypted_len = enc_end;
         }
 
-        // Parse RPC frames
-        while dec.len() >= 12 {
-            let fl = u32::from_le_bytes([dec[0], dec[1], dec[2], dec[3]]) as usize;
-            if fl == 4 { let _ = dec.split_to(4); continue; }
+        // Parse RPC frames out of the decrypted prefix.
+        while decrypted_len >= 12 {
+            let fl = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+            if fl == 4 {
+                let _ = buf.split_to(4);
+                decrypted_len -= 4;
+                continue;
+            }
             if fl < 12 || fl > (1 << 24) {
                 warn!(frame_len = fl, "Invalid RPC frame len");
-                dec.clear();
+                let _ = buf.split_to(decrypted_len);
+                decrypted_len = 0;
                 break;
             }
-            if dec.len() < fl { break; }
+            if decrypted_len < fl { break; }
 
-            let frame = dec.split_to(fl);
+            let frame = buf.split_to(fl);
+            decrypted_len -= fl;
             let pe = fl - 4;
             let ec = u32::from_le_bytes([frame[pe], frame[pe+1], frame[pe+2], frame[pe+3]]);
             if crc32(&frame[..pe]) != ec {
                 warn!("CRC mismatch in data frame");
                 continue;
             }
+            writer.lock().await.record_recv(frame.len() as u64);
 
             let payload = &frame[8..pe];
             if payload.len() < 4 { continue; }
             let pt = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
             let body = &payload[4..];
+            let frame_len = frame.len();
+            let tap = |cid: u64| {
+                if let Some(t) = &frame_tap {
+                    t.capture(pt, cid, frame_len, FrameDirection::Inbound);
+                }
+            };
 
             if pt == RPC_PROXY_ANS_U32 && body.len() >= 12 {
                 let flags = u32::from_le_bytes(body[0..4].try_into().unwrap());
                 let cid = u64::from_le_bytes(body[4..12].try_into().unwrap());
                 let data = Bytes::copy_from_slice(&body[12..]);
                 trace!(cid, len = data.len(), flags, "ANS");
+                tap(cid);
+                dec_in_flight(&writer).await;
                 reg.route(cid, MeResponse::Data(data)).await;
+            } else if pt == RPC_PONG_U32 && body.len() >= 8 {
+                let ping_id = i64::from_le_bytes(body[0..8].try_into().unwrap());
+                tap(0);
+                let mut w = writer.lock().await;
+                if let Some((id, sent_at)) = w.pending_ping {
+                    if id == ping_id {
+                        let sample = sent_at.elapsed();
+                        // rtt = 0.875*rtt + 0.125*sample
+                        w.rtt_estimate = w.rtt_estimate.mul_f64(0.875) + sample.mul_f64(0.125);
+                        w.pending_ping = None;
+                    }
+                }
+                w.last_seen = Instant::now();
+                w.healthy.store(true, Ordering::Relaxed);
+                trace!(ping_id, rtt_ms = w.rtt_estimate.as_millis(), "RPC_PONG received, writer healthy");
             } else if pt == RPC_SIMPLE_ACK_U32 && body.len() >= 12 {
                 let cid = u64::from_le_bytes(body[0..8].try_into().unwrap());
                 let cfm = u32::from_le_bytes(body[8..12].try_into().unwrap());
                 trace!(cid, cfm, "ACK");
+                tap(cid);
+                writer.lock().await.last_seen = Instant::now();
+                dec_in_flight(&writer).await;
                 reg.route(cid, MeResponse::Ack(cfm)).await;
             } else if pt == RPC_CLOSE_EXT_U32 && body.len() >= 8 {
                 let cid = u64::from_le_bytes(body[0..8].try_into().unwrap());
                 debug!(cid, "CLOSE_EXT from ME");
+                tap(cid);
+                dec_in_flight(&writer).await;
                 reg.route(cid, MeResponse::Close).await;
                 reg.unregister(cid).await;
             } else if pt == RPC_CLOSE_CONN_U32 && body.len() >= 8 {
                 let cid = u64::from_le_bytes(body[0..8].try_into().unwrap());
                 debug!(cid, "CLOSE_CONN from ME");
+                tap(cid);
+                dec_in_flight(&writer).await;
                 reg.route(cid, MeResponse::Close).await;
                 reg.unregister(cid).await;
             } else if pt == RPC_PING_U32 && body.len() >= 8 {
                 let ping_id = i64::from_le_bytes(body[0..8].try_into().unwrap());
                 trace!(ping_id, "RPC_PING -> PONG");
+                tap(0);
                 let mut pong = Vec::with_capacity(12);
                 pong.extend_from_slice(&RPC_PONG_U32.to_le_bytes());
                 pong.extend_from_slice(&ping_id.to_le_bytes());
+                rate_limit_wait(&writer, pong.len()).await;
                 if let Err(e) = writer.lock().await.send(&pong).await {
                     warn!(error = %e, "PONG send failed");
                     break;
                 }
             } else {
+                tap(0);
                 debug!(rpc_type = format_args!("0x{:08x}", pt), len = body.len(), "Unknown RPC");
             }
         }
@@ -898,28 +1682,221 @@ pub fn proto_flags_for_tag(tag: crate::protocol::constants::ProtoTag) -> u32 {
 
 // ========== Health Monitor (Phase 4) ==========
 
+/// Per-address reconnect backoff, so a flaky middle-proxy doesn't get
+/// hammered with connect attempts every tick.
+struct BackoffState {
+    consecutive_failures: u32,
+    next_eligible: Instant,
+}
+
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_CAP: Duration = Duration::from_secs(60);
+
+/// +/-20% jitter around `d`, using the wall-clock nanosecond counter as a
+/// cheap, dependency-free entropy source (no crypto strength needed here).
+fn jittered(d: Duration) -> Duration {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    let frac = 0.8 + (nanos % 401) as f64 / 1000.0; // 0.8 ..= 1.2
+    Duration::from_secs_f64(d.as_secs_f64() * frac)
+}
+
+const KEEPALIVE_PING_INTERVAL: Duration = Duration::from_secs(20);
+const KEEPALIVE_PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Plaintext length of a keepalive ping's payload (`RPC_PING` tag + ping id).
+const PING_PAYLOAD_LEN: usize = 12;
+
+/// Ping writers that have gone quiet and evict any whose last ping went
+/// unanswered past `KEEPALIVE_PING_TIMEOUT` — a missed keepalive marks a
+/// writer dead just like a send failure does.
+async fn me_keepalive_sweep(pool: &Arc<MePool>) {
+    let snapshot = pool.writers.read().await.clone();
+    for w in snapshot {
+        let should_ping = {
+            let guard = match w.try_lock() {
+                Ok(g) => g,
+                Err(_) => continue, // mid-send; check again next sweep
+            };
+
+            if guard.keepalive_timed_out(KEEPALIVE_PING_TIMEOUT) {
+                guard.healthy.store(false, Ordering::Relaxed);
+                drop(guard);
+                pool.remove_writer(&w).await;
+                warn!("ME writer evicted: missed keepalive");
+                continue;
+            }
+
+            guard.pending_ping.is_none() && guard.last_seen.elapsed() >= KEEPALIVE_PING_INTERVAL
+        };
+
+        if should_ping {
+            // Wait for the egress cap (if any) before taking the writer
+            // lock again, same as every other send — a ping is tiny, but
+            // reader_loop still shouldn't block on this writer's limiter.
+            rate_limit_wait(&w, PING_PAYLOAD_LEN).await;
+            if let Ok(mut guard) = w.try_lock() {
+                if let Err(e) = guard.send_ping().await {
+                    debug!(error = %e, "keepalive ping send failed");
+                }
+            }
+        }
+    }
+}
+
+/// Poll interval for the health monitor's own tick, bounding the worst-case
+/// reaction time when a writer dies without going through
+/// [`MePool::remove_writer`] (there currently is no such path, but this is
+/// the floor, not the steady-state wakeup source).
+const HEALTH_MONITOR_POLL: Duration = Duration::from_millis(500);
+
+/// Supervisor loop: actively pings idle writers to detect half-dead links,
+/// evicts writers that fail a send or miss a keepalive, and reconnects
+/// missing slots against `TG_MIDDLE_PROXIES_FLAT_V4` with jittered
+/// exponential backoff per address so a network blip doesn't turn into a
+/// reconnect storm. Reacts promptly to a writer disappearing (woken via
+/// `pool.writer_removed` as soon as it's evicted) rather than waiting on a
+/// fixed tick, while `HEALTH_MONITOR_POLL` still bounds the worst case.
 pub async fn me_health_monitor(
     pool: Arc<MePool>,
     rng: Arc<SecureRandom>,
     min_connections: usize,
 ) {
+    let mut backoff: HashMap<SocketAddr, BackoffState> = HashMap::new();
+    let mut was_below_min = false;
+
     loop {
-        tokio::time::sleep(Duration::from_secs(30)).await;
+        tokio::select! {
+            _ = tokio::time::sleep(HEALTH_MONITOR_POLL) => {}
+            _ = pool.writer_removed.notified() => {}
+        }
+
+        me_keepalive_sweep(&pool).await;
+
         let current = pool.writers.read().await.len();
-        if current < min_connections {
+        if current >= min_connections {
+            was_below_min = false;
+            continue;
+        }
+        if !was_below_min {
             warn!(current, min = min_connections, "ME pool below minimum, reconnecting...");
-            let addrs = TG_MIDDLE_PROXIES_FLAT_V4.clone();
-            for &(ip, port) in addrs.iter() {
-                let needed = min_connections.saturating_sub(pool.writers.read().await.len());
-                if needed == 0 { break; }
-                for _ in 0..needed {
-                    let addr = SocketAddr::new(ip, port);
-                    match pool.connect_one(addr, &rng).await {
-                        Ok(()) => info!(%addr, "ME reconnected"),
-                        Err(e) => debug!(%addr, error = %e, "ME reconnect failed"),
+            was_below_min = true;
+        }
+
+        let now = Instant::now();
+        for &(ip, port) in TG_MIDDLE_PROXIES_FLAT_V4.iter() {
+            if pool.writers.read().await.len() >= min_connections {
+                break;
+            }
+            let addr = SocketAddr::new(ip, port);
+            if backoff.get(&addr).is_some_and(|b| b.next_eligible > now) {
+                continue;
+            }
+
+            match pool.connect_one(addr, &rng).await {
+                Ok(()) => {
+                    info!(%addr, "ME reconnected");
+                    backoff.remove(&addr);
+                }
+                Err(e) => {
+                    let state = backoff.entry(addr).or_insert_with(|| BackoffState {
+                        consecutive_failures: 0,
+                        next_eligible: now,
+                    });
+                    state.consecutive_failures += 1;
+                    let exp = BACKOFF_BASE
+                        .saturating_mul(1u32 << state.consecutive_failures.min(6))
+                        .min(BACKOFF_CAP);
+                    let delay = jittered(exp);
+                    state.next_eligible = now + delay;
+                    debug!(
+                        %addr, error = %e,
+                        failures = state.consecutive_failures,
+                        delay_ms = delay.as_millis(),
+                        "ME reconnect failed, backing off"
+                    );
+                }
+            }
+        }
+    }
+}
+
+// ========== Proactive Rekey/Rotation ==========
+
+/// How often [`drain_and_close_writer`] re-checks a rotated-out writer's
+/// `in_flight` count while waiting for it to drain.
+const REKEY_DRAIN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Upper bound on how long a rotated-out writer is kept alive waiting for
+/// its in-flight requests to drain. Backstops the case this was written
+/// to rely on the peer for — a quiet ME that never closes its end of a
+/// link with nothing left in flight — by forcing the close anyway.
+const REKEY_DRAIN_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Wait for `old`'s in-flight requests to finish (so responses already
+/// underway still reach their registered `conn_id` via the shared
+/// `ConnRegistry`), then actually close it: abort its reader task and
+/// shut down its socket via [`MePool::remove_writer`]. `old` is already
+/// out of the routing set by the time this runs, so the only thing this
+/// adds is the cleanup `remove_writer` performs beyond the `Vec` removal.
+async fn drain_and_close_writer(pool: Arc<MePool>, old: Arc<Mutex<RpcWriter>>, addr: SocketAddr) {
+    let deadline = Instant::now() + REKEY_DRAIN_TIMEOUT;
+    loop {
+        let in_flight = old.lock().await.in_flight.load(Ordering::Relaxed);
+        if in_flight == 0 {
+            break;
+        }
+        if Instant::now() >= deadline {
+            warn!(%addr, in_flight, "ME writer rekey drain timed out, forcing close");
+            break;
+        }
+        tokio::time::sleep(REKEY_DRAIN_POLL_INTERVAL).await;
+    }
+    pool.remove_writer(&old).await;
+    info!(%addr, "ME writer rotated out after rekey fully closed");
+}
+
+/// Periodically scan the writer pool for connections that have crossed
+/// their usage/age threshold and rotate them: connect a fresh handshake to
+/// the same address, then stop routing new `RPC_PROXY_REQ`s to the old
+/// writer. The old writer's reader task is left running rather than
+/// killed outright, so any response already in flight on that socket
+/// still gets delivered to its registered `conn_id` via the shared
+/// `ConnRegistry`; [`drain_and_close_writer`] then closes it for real once
+/// that drains (or a timeout forces it), rather than just hoping the ME
+/// closes its end on its own.
+pub async fn me_rekey_task(pool: Arc<MePool>, rng: Arc<SecureRandom>) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+
+        let stale: Vec<(Arc<Mutex<RpcWriter>>, SocketAddr)> = {
+            let ws = pool.writers.read().await;
+            let mut stale = Vec::new();
+            for w in ws.iter() {
+                // Best-effort peek: skip a writer that's mid-send rather
+                // than blocking the scan on the hot path's lock.
+                if let Ok(guard) = w.try_lock() {
+                    if guard.exceeds(pool.rekey_max_bytes, pool.rekey_max_age) {
+                        stale.push((w.clone(), guard.addr));
                     }
                 }
             }
+            stale
+        };
+
+        for (old, addr) in stale {
+            match pool.connect_one(addr, &rng).await {
+                Ok(()) => {
+                    let mut ws = pool.writers.write().await;
+                    ws.retain(|w| !Arc::ptr_eq(w, &old));
+                    drop(ws);
+                    info!(%addr, "ME writer rotated after crossing rekey threshold");
+                    tokio::spawn(drain_and_close_writer(pool.clone(), old, addr));
+                }
+                Err(e) => warn!(%addr, error = %e, "ME rekey reconnect failed, keeping old writer"),
+            }
         }
     }
 }